@@ -0,0 +1,70 @@
+use std::hint::black_box;
+use std::time::{Duration, Instant};
+
+use rschip8::prelude::*;
+
+const ROUNDS: u32 = 200;
+const STEPS: u32 = 10_000_000;
+
+// LD V0, 0; ADD V0, 1; ADD V1, V0; SE V0, 0; JP 0x202; JP 0x200
+const ROM: [u8; 12] = [0x60, 0x00, 0x70, 0x01, 0x81, 0x04, 0x30, 0x00, 0x12, 0x02, 0x12, 0x00];
+
+fn time(rounds: u32, mut f: impl FnMut()) -> Duration {
+    let start = Instant::now();
+    for _ in 0..rounds {
+        f();
+    }
+    start.elapsed()
+}
+
+fn per(elapsed: Duration, count: u64) -> f64 {
+    elapsed.as_secs_f64() * 1e9 / count as f64
+}
+
+fn report(engine: &str, elapsed: Duration) {
+    println!(
+        "{engine:<7} {:<13} {:6.2} ns per instruction, {:.0} million per second",
+        "counting loop",
+        per(elapsed, u64::from(STEPS)),
+        f64::from(STEPS) / elapsed.as_secs_f64() / 1e6,
+    );
+}
+
+fn main() {
+    // Built outside the timings.
+    black_box(Op::decode(0));
+
+    // Every word, and the words of a ROM that mostly runs arithmetic.
+    let all = (0..=u16::MAX).collect::<Vec<_>>();
+    let mut rng = Xorshift::new(1);
+    let common = (0..all.len())
+        .map(|_| [0x6000, 0x7000, 0x8004, 0x3000, 0x1200, 0xa200, 0xd015, 0xf01e][usize::from(rng.next_u8() & 7)] | u16::from(rng.next_u8() & 0x0f))
+        .collect::<Vec<_>>();
+
+    for (name, words) in [("every word", &all), ("common words", &common)] {
+        let count = u64::from(ROUNDS) * words.len() as u64;
+        let chain = time(ROUNDS, || words.iter().for_each(|w| { black_box(Op::decode_uncached(black_box(*w))); }));
+        let table = time(ROUNDS, || words.iter().for_each(|w| { black_box(Op::decode(black_box(*w))); }));
+        println!(
+            "decode  {name:<13} if-else chain {:6.2} ns  table {:6.2} ns  {:.1}x",
+            per(chain, count),
+            per(table, count),
+            chain.as_secs_f64() / table.as_secs_f64(),
+        );
+    }
+
+    let mut cpu = Cpu::new(&ROM).unwrap();
+    let run = time(STEPS, || {
+        let (_, _, op) = cpu.current();
+        cpu.step(&op).unwrap();
+    });
+    report("step", run);
+
+    #[cfg(feature = "dynarec")]
+    {
+        let mut cpu = Cpu::new(&ROM).unwrap();
+        let mut dynarec = Dynarec::new();
+        let run = time(1, || assert_eq!(dynarec.run(&mut cpu, STEPS).unwrap(), STEPS));
+        report("dynarec", run);
+    }
+}
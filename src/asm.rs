@@ -0,0 +1,540 @@
+use std::collections::HashMap;
+use std::fmt;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::rc::Rc;
+
+use crate::{Op, PROGRAM_BASE, RAM_SIZE};
+
+// Includes nested deeper than this are taken to be a cycle.
+const MAX_INCLUDE_DEPTH: usize = 16;
+// Constants defined through more constants than this are taken to be a cycle.
+const MAX_EXPR_DEPTH: usize = 64;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AsmError {
+    pub file: String,
+    pub line: usize,
+    pub col: usize,
+    pub message: String,
+}
+
+impl fmt::Display for AsmError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}:{}:{}: {}", self.file, self.line, self.col, self.message)
+    }
+}
+
+impl std::error::Error for AsmError {}
+
+// Where in which source file something came from, shared with the Octo
+// front end.
+#[derive(Debug, Clone)]
+pub(crate) struct Loc {
+    pub(crate) file: Rc<str>,
+    pub(crate) line: usize,
+    pub(crate) col: usize,
+}
+
+impl Loc {
+    pub(crate) fn at(&self, col: usize) -> Self {
+        Self { col, ..self.clone() }
+    }
+
+    pub(crate) fn error(&self, message: impl Into<String>) -> AsmError {
+        AsmError { file: self.file.to_string(), line: self.line, col: self.col, message: message.into() }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Token {
+    Ident(String),
+    Number(i64),
+    Str(String),
+    Punct(char),
+}
+
+// Tokens of one line with their 1-based columns, comments stripped.
+fn tokenize(text: &str, loc: &Loc) -> Result<Vec<(Token, usize)>, AsmError> {
+    let chars = text.chars().collect::<Vec<_>>();
+    let mut tokens = Vec::new();
+    let mut n = 0;
+
+    while n < chars.len() {
+        let c = chars[n];
+        let col = n + 1;
+        if c == ';' {
+            break;
+        } else if c.is_whitespace() {
+            n += 1;
+        } else if c.is_ascii_digit() {
+            let start = n;
+            while n < chars.len() && (chars[n].is_ascii_alphanumeric() || chars[n] == '_') {
+                n += 1;
+            }
+            let word = chars[start..n].iter().filter(|c| **c != '_').collect::<String>();
+            let value = parse_number(&word).ok_or_else(|| loc.at(col).error(format!("invalid number `{word}`")))?;
+            tokens.push((Token::Number(value), col));
+        } else if c.is_alphabetic() || c == '_' || c == '.' {
+            let start = n;
+            while n < chars.len() && (chars[n].is_alphanumeric() || chars[n] == '_' || chars[n] == '.') {
+                n += 1;
+            }
+            tokens.push((Token::Ident(chars[start..n].iter().collect()), col));
+        } else if c == '"' {
+            let end = chars[n + 1..].iter().position(|c| *c == '"')
+                .ok_or_else(|| loc.at(col).error("unterminated string"))?;
+            tokens.push((Token::Str(chars[n + 1..n + 1 + end].iter().collect()), col));
+            n += end + 2;
+        } else if ",:=+-()[]".contains(c) {
+            tokens.push((Token::Punct(c), col));
+            n += 1;
+        } else {
+            return Err(loc.at(col).error(format!("unexpected character `{c}`")));
+        }
+    }
+
+    Ok(tokens)
+}
+
+fn parse_number(word: &str) -> Option<i64> {
+    let lower = word.to_ascii_lowercase();
+    if let Some(hex) = lower.strip_prefix("0x") {
+        i64::from_str_radix(hex, 16).ok()
+    } else if let Some(bin) = lower.strip_prefix("0b") {
+        i64::from_str_radix(bin, 2).ok()
+    } else {
+        lower.parse().ok()
+    }
+}
+
+#[derive(Debug, Clone)]
+enum Expr {
+    Number(i64),
+    Symbol(String, Loc),
+    Neg(Box<Expr>),
+    Add(Box<Expr>, Box<Expr>),
+    Sub(Box<Expr>, Box<Expr>),
+}
+
+#[derive(Debug, Clone)]
+enum Operand {
+    V(usize),
+    I,
+    IndirectI,
+    Dt,
+    St,
+    K,
+    F,
+    B,
+    Hf,
+    R,
+    Long(Expr),
+    Expr(Expr),
+}
+
+#[derive(Debug)]
+enum Item {
+    Instruction { mnemonic: String, operands: Vec<(Operand, Loc)> },
+    Bytes(Vec<(Expr, Loc)>),
+    Words(Vec<(Expr, Loc)>),
+}
+
+impl Item {
+    fn len(&self) -> usize {
+        match self {
+            Item::Instruction { operands, .. } if operands.iter().any(|(op, _)| matches!(op, Operand::Long(_))) => 4,
+            Item::Instruction { .. } => 2,
+            Item::Bytes(bytes) => bytes.len(),
+            Item::Words(words) => words.len() * 2,
+        }
+    }
+}
+
+#[derive(Debug)]
+enum Symbol {
+    Label(usize),
+    Constant(Expr),
+}
+
+// Parses a line's tokens from `pos`, tracking where the last one ended for
+// errors at the end of the line.
+struct Parser<'a> {
+    tokens: &'a [(Token, usize)],
+    pos: usize,
+    loc: &'a Loc,
+}
+
+impl Parser<'_> {
+    fn peek(&self) -> Option<&Token> {
+        self.tokens.get(self.pos).map(|(token, _)| token)
+    }
+
+    fn col(&self) -> usize {
+        self.tokens.get(self.pos).map_or_else(|| self.tokens.last().map_or(1, |(_, col)| col + 1), |(_, col)| *col)
+    }
+
+    fn here(&self) -> Loc {
+        self.loc.at(self.col())
+    }
+
+    fn next(&mut self) -> Option<Token> {
+        let token = self.tokens.get(self.pos).map(|(token, _)| token.clone());
+        self.pos += 1;
+        token
+    }
+
+    fn eat(&mut self, c: char) -> bool {
+        if self.peek() == Some(&Token::Punct(c)) {
+            self.pos += 1;
+            true
+        } else {
+            false
+        }
+    }
+
+    fn done(&self) -> bool {
+        self.pos >= self.tokens.len()
+    }
+
+    fn expr(&mut self) -> Result<Expr, AsmError> {
+        let mut expr = self.term()?;
+        loop {
+            if self.eat('+') {
+                expr = Expr::Add(Box::new(expr), Box::new(self.term()?));
+            } else if self.eat('-') {
+                expr = Expr::Sub(Box::new(expr), Box::new(self.term()?));
+            } else {
+                return Ok(expr);
+            }
+        }
+    }
+
+    fn term(&mut self) -> Result<Expr, AsmError> {
+        let loc = self.here();
+        match self.next() {
+            Some(Token::Number(n)) => Ok(Expr::Number(n)),
+            Some(Token::Ident(name)) => Ok(Expr::Symbol(name, loc)),
+            Some(Token::Punct('-')) => Ok(Expr::Neg(Box::new(self.term()?))),
+            Some(Token::Punct('(')) => {
+                let expr = self.expr()?;
+                if !self.eat(')') {
+                    return Err(self.here().error("expected `)`"));
+                }
+                Ok(expr)
+            }
+            _ => Err(loc.error("expected a number or a name")),
+        }
+    }
+
+    fn operand(&mut self) -> Result<(Operand, Loc), AsmError> {
+        let loc = self.here();
+        if self.eat('[') {
+            let register = self.next();
+            if !matches!(register, Some(Token::Ident(ref i)) if i.eq_ignore_ascii_case("i")) || !self.eat(']') {
+                return Err(loc.error("expected `[I]`"));
+            }
+            return Ok((Operand::IndirectI, loc));
+        }
+
+        if let Some(Token::Ident(name)) = self.peek() {
+            let lower = name.to_ascii_lowercase();
+            let register = match lower.as_str() {
+                "i"  => Some(Operand::I),
+                "dt" => Some(Operand::Dt),
+                "st" => Some(Operand::St),
+                "k"  => Some(Operand::K),
+                "f"  => Some(Operand::F),
+                "b"  => Some(Operand::B),
+                "hf" => Some(Operand::Hf),
+                "r"  => Some(Operand::R),
+                _ => lower.strip_prefix('v')
+                    .filter(|n| n.len() == 1)
+                    .and_then(|n| usize::from_str_radix(n, 16).ok())
+                    .map(Operand::V),
+            };
+            if let Some(register) = register {
+                self.pos += 1;
+                return Ok((register, loc));
+            }
+            if lower == "long" {
+                self.pos += 1;
+                return Ok((Operand::Long(self.expr()?), loc));
+            }
+        }
+
+        Ok((Operand::Expr(self.expr()?), loc))
+    }
+
+    fn list<T>(&mut self, mut item: impl FnMut(&mut Self) -> Result<T, AsmError>) -> Result<Vec<T>, AsmError> {
+        let mut items = Vec::new();
+        if self.done() {
+            return Ok(items);
+        }
+        loop {
+            items.push(item(self)?);
+            if self.done() {
+                return Ok(items);
+            }
+            if !self.eat(',') {
+                return Err(self.here().error("expected `,`"));
+            }
+        }
+    }
+}
+
+// Two passes: the first lays out labels and data, the second evaluates
+// operands once every label is known.
+#[derive(Default)]
+struct Assembler {
+    items: Vec<(usize, Loc, Item)>,
+    symbols: HashMap<String, (Symbol, Loc)>,
+    addr: usize,
+}
+
+impl Assembler {
+    fn new() -> Self {
+        Self { addr: PROGRAM_BASE, ..Self::default() }
+    }
+
+    fn define(&mut self, name: &str, symbol: Symbol, loc: &Loc) -> Result<(), AsmError> {
+        if let Some((_, first)) = self.symbols.get(name) {
+            return Err(loc.error(format!("`{name}` is already defined at {}:{}", first.line, first.col)));
+        }
+        self.symbols.insert(name.to_string(), (symbol, loc.clone()));
+        Ok(())
+    }
+
+    fn source(&mut self, file: &Path, text: &str, depth: usize) -> Result<(), AsmError> {
+        let name: Rc<str> = file.to_string_lossy().into();
+        for (n, line) in text.lines().enumerate() {
+            let loc = Loc { file: name.clone(), line: n + 1, col: 1 };
+            let tokens = tokenize(line, &loc)?;
+            self.line(file, Parser { tokens: &tokens, pos: 0, loc: &loc }, depth)?;
+        }
+        Ok(())
+    }
+
+    fn line(&mut self, file: &Path, mut p: Parser, depth: usize) -> Result<(), AsmError> {
+        if let (Some(Token::Ident(name)), Some((Token::Punct(':'), _))) = (p.peek().cloned(), p.tokens.get(1)) {
+            let loc = p.here();
+            self.define(&name, Symbol::Label(self.addr), &loc)?;
+            p.pos += 2;
+        }
+
+        if p.done() {
+            return Ok(());
+        }
+        let loc = p.here();
+        let Some(Token::Ident(word)) = p.next() else {
+            return Err(loc.error("expected an instruction or directive"));
+        };
+
+        if p.eat('=') {
+            let expr = p.expr()?;
+            if !p.done() {
+                return Err(p.here().error("unexpected text after constant"));
+            }
+            return self.define(&word, Symbol::Constant(expr), &loc);
+        }
+
+        let item = match word.to_ascii_lowercase().as_str() {
+            "include" => {
+                let (Some(Token::Str(path)), true) = (p.next(), p.done()) else {
+                    return Err(loc.error("expected `include \"file\"`"));
+                };
+                if depth >= MAX_INCLUDE_DEPTH {
+                    return Err(loc.error("includes nested too deeply"));
+                }
+                let path = file.parent().unwrap_or(Path::new("")).join(path);
+                let text = fs::read_to_string(&path)
+                    .map_err(|e| loc.error(format!("{}: {e}", path.display())))?;
+                return self.source(&path, &text, depth + 1);
+            }
+            "db" => Item::Bytes(p.list(|p| {
+                if let Some(Token::Str(s)) = p.peek().cloned() {
+                    let loc = p.here();
+                    p.pos += 1;
+                    return Ok(s.bytes().map(|b| (Expr::Number(i64::from(b)), loc.clone())).collect());
+                }
+                let loc = p.here();
+                Ok(vec![(p.expr()?, loc)])
+            })?.into_iter().flatten().collect()),
+            "dw" => Item::Words(p.list(|p| {
+                let loc = p.here();
+                Ok((p.expr()?, loc))
+            })?),
+            _ => Item::Instruction { mnemonic: word.to_ascii_uppercase(), operands: p.list(Parser::operand)? },
+        };
+
+        self.addr += item.len();
+        if self.addr > RAM_SIZE {
+            return Err(loc.error("program does not fit in memory"));
+        }
+        self.items.push((self.addr - item.len(), loc, item));
+        Ok(())
+    }
+
+    // Overflow is reported at `loc`, the operand being evaluated.
+    fn eval(&self, expr: &Expr, loc: &Loc, depth: usize) -> Result<i64, AsmError> {
+        let value = match expr {
+            Expr::Number(n) => Some(*n),
+            Expr::Neg(e) => self.eval(e, loc, depth)?.checked_neg(),
+            Expr::Add(a, b) => self.eval(a, loc, depth)?.checked_add(self.eval(b, loc, depth)?),
+            Expr::Sub(a, b) => self.eval(a, loc, depth)?.checked_sub(self.eval(b, loc, depth)?),
+            Expr::Symbol(name, at) => match self.symbols.get(name) {
+                Some((Symbol::Label(addr), _)) => Some(i64::try_from(*addr).unwrap()),
+                Some((Symbol::Constant(_), _)) if depth >= MAX_EXPR_DEPTH => {
+                    return Err(at.error(format!("`{name}` is defined in terms of itself")));
+                }
+                Some((Symbol::Constant(e), _)) => Some(self.eval(e, loc, depth + 1)?),
+                None => return Err(at.error(format!("undefined name `{name}`"))),
+            },
+        };
+        value.ok_or_else(|| loc.error("arithmetic overflow"))
+    }
+
+    fn value(&self, expr: &Expr, loc: &Loc, min: i64, max: i64) -> Result<usize, AsmError> {
+        let value = self.eval(expr, loc, 0)?;
+        if !(min..=max).contains(&value) {
+            return Err(loc.error(format!("{value} is out of range {min}..={max}")));
+        }
+        // Negative bytes wrap around to their two's complement.
+        Ok(usize::try_from(value.rem_euclid(max + 1)).unwrap())
+    }
+
+    fn byte(&self, expr: &Expr, loc: &Loc) -> Result<u8, AsmError> {
+        Ok(u8::try_from(self.value(expr, loc, -128, 0xff)?).unwrap())
+    }
+
+    fn nibble(&self, expr: &Expr, loc: &Loc) -> Result<u8, AsmError> {
+        Ok(u8::try_from(self.value(expr, loc, 0, 0xf)?).unwrap())
+    }
+
+    fn addr(&self, expr: &Expr, loc: &Loc) -> Result<usize, AsmError> {
+        self.value(expr, loc, 0, 0xfff)
+    }
+
+    #[allow(clippy::too_many_lines)]
+    fn instruction(&self, mnemonic: &str, operands: &[(Operand, Loc)], loc: &Loc) -> Result<Op, AsmError> {
+        use Operand::{Dt, Expr as E, Hf, IndirectI, Long, B, F, I, K, R, St, V};
+
+        let ops = operands.iter().map(|(op, _)| op).collect::<Vec<_>>();
+        let at = |n: usize| &operands[n].1;
+        let op = match (mnemonic, &ops[..]) {
+            ("CLS", [])              => Op::CLS {},
+            ("RET", [])              => Op::RET {},
+            ("SCR", [])              => Op::SCR {},
+            ("SCL", [])              => Op::SCL {},
+            ("EXIT", [])             => Op::EXIT {},
+            ("LOW", [])              => Op::LOW {},
+            ("HIGH", [])             => Op::HIGH {},
+            ("AUDIO", [])            => Op::AUDIO {},
+            ("SCD", [E(n)])          => Op::SCD_nibble { n: self.nibble(n, at(0))? },
+            ("SCU", [E(n)])          => Op::SCU_nibble { n: self.nibble(n, at(0))? },
+            ("PLANE", [E(n)])        => Op::PLANE_n { n: self.nibble(n, at(0))? },
+            ("JP", [E(a)])           => Op::JP_addr { nnn: self.addr(a, at(0))? },
+            ("JP", [V(0), E(a)])     => Op::JP_V0_addr { nnn: self.addr(a, at(1))? },
+            ("CALL", [E(a)])         => Op::CALL_addr { nnn: self.addr(a, at(0))? },
+            ("SE", [V(x), E(nn)])    => Op::SE_Vx_byte { x: *x, nn: self.byte(nn, at(1))? },
+            ("SE", [V(x), V(y)])     => Op::SE_Vx_Vy { x: *x, y: *y },
+            ("SNE", [V(x), E(nn)])   => Op::SNE_Vx_byte { x: *x, nn: self.byte(nn, at(1))? },
+            ("SNE", [V(x), V(y)])    => Op::SNE_Vx_Vy { x: *x, y: *y },
+            ("LD", [V(x), E(nn)])    => Op::LD_Vx_byte { x: *x, nn: self.byte(nn, at(1))? },
+            ("LD", [V(x), V(y)])     => Op::LD_Vx_Vy { x: *x, y: *y },
+            ("LD", [I, E(a)])        => Op::LD_I_addr { nnn: self.addr(a, at(1))? },
+            ("LD", [I, Long(a)])     => Op::LD_I_long { nnnn: self.value(a, at(1), 0, 0xffff)? },
+            ("LD", [V(x), Dt])       => Op::LD_Vx_DT { x: *x },
+            ("LD", [Dt, V(x)])       => Op::LD_DT_Vx { x: *x },
+            ("LD", [St, V(x)])       => Op::LD_ST_Vx { x: *x },
+            ("LD", [V(x), K])        => Op::LD_Vx_K { x: *x },
+            ("LD", [F, V(x)])        => Op::LD_F_Vx { x: *x },
+            ("LD", [Hf, V(x)])       => Op::LD_HF_Vx { x: *x },
+            ("LD", [B, V(x)])        => Op::LD_B_Vx { x: *x },
+            ("LD", [IndirectI, V(x)]) => Op::LD_I_Vx { x: *x },
+            ("LD", [V(x), IndirectI]) => Op::LD_Vx_I { x: *x },
+            ("LD", [R, V(x)])        => Op::LD_R_Vx { x: *x },
+            ("LD", [V(x), R])        => Op::LD_Vx_R { x: *x },
+            ("ADD", [V(x), E(nn)])   => Op::ADD_Vx_byte { x: *x, nn: self.byte(nn, at(1))? },
+            ("ADD", [V(x), V(y)])    => Op::ADD_Vx_Vy { x: *x, y: *y },
+            ("ADD", [I, V(x)])       => Op::ADD_I_Vx { x: *x },
+            ("SUB", [V(x), V(y)])    => Op::SUB_Vx_Vy { x: *x, y: *y },
+            ("SUBN", [V(x), V(y)])   => Op::SUBN_Vx_Vy { x: *x, y: *y },
+            ("AND", [V(x), V(y)])    => Op::AND_Vx_Vy { x: *x, y: *y },
+            ("OR", [V(x), V(y)])     => Op::OR_Vx_Vy { x: *x, y: *y },
+            ("XOR", [V(x), V(y)])    => Op::XOR_Vx_Vy { x: *x, y: *y },
+            ("SHR", [V(x)])          => Op::SHR_Vx_Vy { x: *x, y: *x },
+            ("SHR", [V(x), V(y)])    => Op::SHR_Vx_Vy { x: *x, y: *y },
+            ("SHL", [V(x)])          => Op::SHL_Vx_Vy { x: *x, y: *x },
+            ("SHL", [V(x), V(y)])    => Op::SHL_Vx_Vy { x: *x, y: *y },
+            ("RND", [V(x), E(nn)])   => Op::RND_Vx_byte { x: *x, nn: self.byte(nn, at(1))? },
+            ("SKP", [V(x)])          => Op::SKP_Vx { x: *x },
+            ("SKNP", [V(x)])         => Op::SKNP_Vx { x: *x },
+            ("DRW", [V(x), V(y), E(n)]) => Op::DRW_Vx_Vy_nibble { x: *x, y: *y, n: self.nibble(n, at(2))? },
+            ("SAVE", [V(x), V(y)])   => Op::SAVE_Vx_Vy { x: *x, y: *y },
+            ("LOAD", [V(x), V(y)])   => Op::LOAD_Vx_Vy { x: *x, y: *y },
+            ("PITCH", [V(x)])        => Op::PITCH_Vx { x: *x },
+            _ if KNOWN.contains(&mnemonic) => return Err(loc.error(format!("invalid operands for {mnemonic}"))),
+            _ => return Err(loc.error(format!("unknown instruction `{mnemonic}`"))),
+        };
+
+        Ok(op)
+    }
+
+    fn output(&self) -> Result<Vec<u8>, AsmError> {
+        let mut rom = Vec::with_capacity(self.addr - PROGRAM_BASE);
+        for (_, loc, item) in &self.items {
+            match item {
+                Item::Instruction { mnemonic, operands } => {
+                    rom.extend(self.instruction(mnemonic, operands, loc)?.to_bytes());
+                }
+                Item::Bytes(bytes) => {
+                    for (expr, loc) in bytes {
+                        rom.push(self.byte(expr, loc)?);
+                    }
+                }
+                Item::Words(words) => {
+                    for (expr, loc) in words {
+                        let word = u16::try_from(self.value(expr, loc, -0x8000, 0xffff)?).unwrap();
+                        rom.extend(word.to_be_bytes());
+                    }
+                }
+            }
+        }
+        Ok(rom)
+    }
+}
+
+const KNOWN: [&str; 31] = [
+    "CLS", "RET", "SCR", "SCL", "EXIT", "LOW", "HIGH", "AUDIO", "SCD", "SCU", "PLANE", "JP", "CALL", "SE", "SNE",
+    "LD", "ADD", "SUB", "SUBN", "AND", "OR", "XOR", "SHR", "SHL", "RND", "SKP", "SKNP", "DRW", "SAVE", "LOAD", "PITCH",
+];
+
+// Assembles the mnemonics `Op` displays as, plus `name:` labels,
+// `name = expr` constants, `db`/`dw` data and `include "file"`. The ROM is
+// laid out from `PROGRAM_BASE`. Includes are relative to the current
+// directory.
+pub fn assemble(source: &str) -> Result<Vec<u8>, AsmError> {
+    let mut asm = Assembler::new();
+    asm.source(Path::new("<source>"), source, 0)?;
+    asm.output()
+}
+
+// Like `assemble`, with includes relative to the including file.
+pub fn assemble_file(path: &str) -> Result<Vec<u8>, AsmError> {
+    let path = PathBuf::from(path);
+    let text = read_source(&path)?;
+
+    let mut asm = Assembler::new();
+    asm.source(&path, &text, 0)?;
+    asm.output()
+}
+
+pub(crate) fn read_source(path: &Path) -> Result<String, AsmError> {
+    fs::read_to_string(path).map_err(|e| AsmError {
+        file: path.display().to_string(),
+        line: 0,
+        col: 0,
+        message: e.to_string(),
+    })
+}
@@ -0,0 +1,214 @@
+use std::io::{self, Seek, SeekFrom, Write};
+use std::process::{Child, ChildStdin, Command, Stdio};
+
+use crate::Timers;
+use crate::timer::TIMER_HZ;
+
+pub const SAMPLE_RATE: u32 = 44_100;
+pub const SAMPLES_PER_FRAME: usize = (SAMPLE_RATE / TIMER_HZ) as usize;
+pub const BEEP_HZ: f32 = 440.0;
+pub const BEEP_VOLUME: i16 = 0x1000;
+
+// Consumes signed 16-bit mono samples at SAMPLE_RATE.
+pub trait AudioSink {
+    fn write(&mut self, samples: &[i16]) -> io::Result<()>;
+}
+
+impl<S: AudioSink + ?Sized> AudioSink for Box<S> {
+    fn write(&mut self, samples: &[i16]) -> io::Result<()> {
+        (**self).write(samples)
+    }
+}
+
+impl AudioSink for Vec<i16> {
+    fn write(&mut self, samples: &[i16]) -> io::Result<()> {
+        self.extend_from_slice(samples);
+        Ok(())
+    }
+}
+
+#[derive(Debug, Default)]
+pub struct NullSink;
+
+impl AudioSink for NullSink {
+    fn write(&mut self, _samples: &[i16]) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+// Writes a PCM WAV file. The RIFF sizes are patched in `finish`, or on drop.
+#[derive(Debug)]
+pub struct WavSink<W: Write + Seek> {
+    out: Option<W>,
+    data_len: u32,
+}
+
+impl<W: Write + Seek> WavSink<W> {
+    pub fn new(mut out: W) -> io::Result<Self> {
+        Self::write_header(&mut out, 0)?;
+        Ok(Self { out: Some(out), data_len: 0 })
+    }
+
+    pub fn finish(mut self) -> io::Result<W> {
+        let mut out = self.out.take().ok_or(io::ErrorKind::BrokenPipe)?;
+        out.seek(SeekFrom::Start(0))?;
+        Self::write_header(&mut out, self.data_len)?;
+        out.seek(SeekFrom::End(0))?;
+        out.flush()?;
+        Ok(out)
+    }
+
+    fn write_header(out: &mut W, data_len: u32) -> io::Result<()> {
+        out.write_all(b"RIFF")?;
+        out.write_all(&(36 + data_len).to_le_bytes())?;
+        out.write_all(b"WAVEfmt ")?;
+        out.write_all(&16u32.to_le_bytes())?;
+        out.write_all(&1u16.to_le_bytes())?; // PCM
+        out.write_all(&1u16.to_le_bytes())?; // mono
+        out.write_all(&SAMPLE_RATE.to_le_bytes())?;
+        out.write_all(&(SAMPLE_RATE * 2).to_le_bytes())?;
+        out.write_all(&2u16.to_le_bytes())?;
+        out.write_all(&16u16.to_le_bytes())?;
+        out.write_all(b"data")?;
+        out.write_all(&data_len.to_le_bytes())
+    }
+}
+
+impl<W: Write + Seek> AudioSink for WavSink<W> {
+    fn write(&mut self, samples: &[i16]) -> io::Result<()> {
+        let out = self.out.as_mut().ok_or(io::ErrorKind::BrokenPipe)?;
+        for sample in samples {
+            out.write_all(&sample.to_le_bytes())?;
+        }
+        self.data_len = self.data_len.saturating_add(u32::try_from(samples.len() * 2).unwrap_or(u32::MAX));
+        Ok(())
+    }
+}
+
+impl<W: Write + Seek> Drop for WavSink<W> {
+    fn drop(&mut self) {
+        if let Some(mut out) = self.out.take() {
+            let _ = out.seek(SeekFrom::Start(0))
+                .and_then(|_| Self::write_header(&mut out, self.data_len))
+                .and_then(|()| out.flush());
+        }
+    }
+}
+
+// Streams raw samples to `aplay` from alsa-utils, one child process per
+// sink. That makes it a Linux/ALSA backend only, but keeps the core free of
+// native audio dependencies; elsewhere use `NullSink` or `WavSink`.
+#[derive(Debug)]
+pub struct DeviceSink {
+    player: Child,
+    stdin: ChildStdin,
+}
+
+impl DeviceSink {
+    pub fn open() -> io::Result<Self> {
+        let rate = SAMPLE_RATE.to_string();
+        Self::spawn(Command::new("aplay")
+            .args(["-q", "-t", "raw", "-f", "S16_LE", "-c", "1", "-r", rate.as_str()]))
+            .map_err(|e| match e.kind() {
+                io::ErrorKind::NotFound => io::Error::new(e.kind(), "aplay not found, the device sink needs alsa-utils"),
+                _ => e,
+            })
+    }
+
+    pub fn spawn(command: &mut Command) -> io::Result<Self> {
+        let mut player = command
+            .stdin(Stdio::piped())
+            .stdout(Stdio::null())
+            .stderr(Stdio::null())
+            .spawn()?;
+        let stdin = player.stdin.take().ok_or(io::ErrorKind::BrokenPipe)?;
+
+        Ok(Self { player, stdin })
+    }
+}
+
+impl AudioSink for DeviceSink {
+    fn write(&mut self, samples: &[i16]) -> io::Result<()> {
+        let bytes = samples.iter().flat_map(|s| s.to_le_bytes()).collect::<Vec<_>>();
+        self.stdin.write_all(&bytes)
+    }
+}
+
+impl Drop for DeviceSink {
+    fn drop(&mut self) {
+        let _ = self.player.kill();
+        let _ = self.player.wait();
+    }
+}
+
+// XO-CHIP 1-bit audio: 128 samples played back, looping, at
+// 4000 * 2^((pitch - 64) / 48) Hz.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct AudioPattern {
+    pub bits: [u8; 16],
+    pub pitch: u8,
+}
+
+impl Default for AudioPattern {
+    fn default() -> Self {
+        Self { bits: [0; 16], pitch: 64 }
+    }
+}
+
+impl AudioPattern {
+    #[must_use]
+    pub fn rate(&self) -> f32 {
+        4000.0 * 2f32.powf((f32::from(self.pitch) - 64.0) / 48.0)
+    }
+
+    #[must_use]
+    pub fn bit(&self, n: usize) -> bool {
+        self.bits[n / 8 % 16] >> (7 - n % 8) & 1 != 0
+    }
+}
+
+// Square-wave beeper driven by the sound timer, one frame of samples per call.
+// Programs that loaded an XO-CHIP pattern hear that instead of the square wave.
+#[derive(Debug)]
+pub struct Beeper<S: AudioSink> {
+    sink: S,
+    phase: f32,
+}
+
+impl<S: AudioSink> Beeper<S> {
+    pub fn new(sink: S) -> Self {
+        Self { sink, phase: 0.0 }
+    }
+
+    pub fn sink(&self) -> &S {
+        &self.sink
+    }
+
+    pub fn into_sink(self) -> S {
+        self.sink
+    }
+
+    #[allow(clippy::cast_precision_loss, clippy::cast_possible_truncation, clippy::cast_sign_loss)]
+    pub fn frame(&mut self, timers: &Timers, pattern: Option<&AudioPattern>) -> io::Result<()> {
+        let mut samples = [0i16; SAMPLES_PER_FRAME];
+
+        if let (true, Some(pattern)) = (timers.sounding(), pattern) {
+            // `phase` runs over the whole 128 bit pattern.
+            let step = pattern.rate() / 128.0 / SAMPLE_RATE as f32;
+            for sample in &mut samples {
+                *sample = if pattern.bit((self.phase * 128.0) as usize) { BEEP_VOLUME } else { -BEEP_VOLUME };
+                self.phase = (self.phase + step).fract();
+            }
+        } else if timers.sounding() {
+            let step = BEEP_HZ / SAMPLE_RATE as f32;
+            for sample in &mut samples {
+                *sample = if self.phase < 0.5 { BEEP_VOLUME } else { -BEEP_VOLUME };
+                self.phase = (self.phase + step).fract();
+            }
+        } else {
+            self.phase = 0.0;
+        }
+
+        self.sink.write(&samples)
+    }
+}
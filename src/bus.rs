@@ -0,0 +1,173 @@
+use std::cell::{Cell, OnceCell};
+use std::ops::Range;
+
+use crate::Op;
+
+// Addresses per page of the decode cache.
+const PAGE: usize = 256;
+
+type Decoded = (usize, u16, Op);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AccessKind {
+    Read,
+    Write,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Access {
+    pub addr: usize,
+    pub kind: AccessKind,
+    pub value: u8,
+}
+
+// RAM behind the CPU. Data reads and writes go through `read`/`write` and are
+// recorded while tracing is on, so the debugger can watch addresses.
+// Instruction fetches, ROM loading and tools use the untraced `peek`/`load`.
+//
+// Instructions are decoded once per address and cached here, where every
+// write drops the ones it touches, so self-modifying code still runs right.
+// The cache is allocated a page at a time, on the first fetch from that
+// page, so a `Cpu` only pays for the memory its code runs from.
+#[derive(Debug, Clone)]
+pub struct Bus {
+    ram: Vec<u8>,
+    tracing: bool,
+    accesses: Vec<Access>,
+    // Entries are stored exactly as `fetch` returns them, because building
+    // a new tuple on every fetch costs more than the cache lookup itself.
+    // Whether an entry is still valid is kept in `cached`, one flag per byte.
+    decoded: Vec<OnceCell<Box<[Cell<Decoded>]>>>,
+    cached: Vec<Cell<bool>>,
+    generation: u64,
+}
+
+impl Bus {
+    #[must_use]
+    pub fn new(size: usize) -> Self {
+        Self {
+            ram: vec![0u8; size],
+            tracing: false,
+            accesses: Vec::new(),
+            decoded: vec![OnceCell::new(); size.div_ceil(PAGE)],
+            cached: vec![Cell::new(false); size],
+            generation: 0,
+        }
+    }
+
+    #[must_use]
+    pub fn len(&self) -> usize {
+        self.ram.len()
+    }
+
+    #[must_use]
+    pub fn is_empty(&self) -> bool {
+        self.ram.is_empty()
+    }
+
+    pub fn read(&mut self, addr: usize) -> u8 {
+        let value = self.ram[addr];
+        self.record(addr, AccessKind::Read, value);
+        value
+    }
+
+    pub fn write(&mut self, addr: usize, value: u8) {
+        self.ram[addr] = value;
+        self.invalidate(addr..addr + 1);
+        self.record(addr, AccessKind::Write, value);
+    }
+
+    pub fn read_slice(&mut self, range: Range<usize>) -> &[u8] {
+        if self.tracing {
+            for addr in range.clone() {
+                self.record(addr, AccessKind::Read, self.ram[addr]);
+            }
+        }
+        &self.ram[range]
+    }
+
+    pub fn write_slice(&mut self, base: usize, data: &[u8]) {
+        self.ram[base..base + data.len()].copy_from_slice(data);
+        self.invalidate(base..base + data.len());
+        if self.tracing {
+            for (addr, value) in (base..).zip(data) {
+                self.record(addr, AccessKind::Write, *value);
+            }
+        }
+    }
+
+    #[must_use]
+    pub fn peek(&self, addr: usize) -> u8 {
+        self.ram[addr % self.ram.len()]
+    }
+
+    #[must_use]
+    pub fn peek_slice(&self, range: Range<usize>) -> &[u8] {
+        &self.ram[range]
+    }
+
+    pub fn load(&mut self, base: usize, data: &[u8]) {
+        self.ram[base..base + data.len()].copy_from_slice(data);
+        self.invalidate(base..base + data.len());
+    }
+
+    // `addr` (wrapped like `peek`), the word there and what it decodes to,
+    // `F000 nnnn` included.
+    #[must_use]
+    pub fn fetch(&self, addr: usize) -> (usize, u16, Op) {
+        let addr = if addr < self.ram.len() { addr } else { addr % self.ram.len() };
+        let page = &self.decoded[addr / PAGE];
+        if let (true, Some(page)) = (self.cached[addr].get(), page.get()) {
+            return page[addr % PAGE].get();
+        }
+        let entry = &page.get_or_init(|| vec![Cell::new((0, 0, Op::UNKNOWN {})); PAGE].into())[addr % PAGE];
+
+        let word = |addr: usize| u16::from(self.peek(addr)) << 8 | u16::from(self.peek(addr + 1));
+        let first = word(addr);
+        let op = match Op::decode(first) {
+            Op::LD_I_long { .. } => Op::LD_I_long { nnnn: usize::from(word(addr + 2)) },
+            op => op,
+        };
+        entry.set((addr, first, op));
+        self.cached[addr].set(true);
+        (addr, first, op)
+    }
+
+    // Bumped by every write, so code cached outside the bus can tell when
+    // to look at its bytes again.
+    #[must_use]
+    pub fn generation(&self) -> u64 {
+        self.generation
+    }
+
+    // Instructions are up to 4 bytes long, so those starting up to 3 bytes
+    // before `range` may cover it. Wraps like `peek`.
+    fn invalidate(&mut self, range: Range<usize>) {
+        self.generation += 1;
+        let len = self.ram.len();
+        for addr in range.start + len - 3..range.end + len {
+            self.cached[addr % len].set(false);
+        }
+    }
+
+    #[must_use]
+    pub fn tracing(&self) -> bool {
+        self.tracing
+    }
+
+    pub fn set_tracing(&mut self, tracing: bool) {
+        self.tracing = tracing;
+        self.accesses.clear();
+    }
+
+    // Accesses recorded since the last call.
+    pub fn take_accesses(&mut self) -> Vec<Access> {
+        std::mem::take(&mut self.accesses)
+    }
+
+    fn record(&mut self, addr: usize, kind: AccessKind, value: u8) {
+        if self.tracing {
+            self.accesses.push(Access { addr, kind, value });
+        }
+    }
+}
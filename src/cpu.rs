@@ -1,11 +1,22 @@
-use rand::Rng;
-
 use crate::Video;
 use crate::Op;
+use crate::Timers;
+use crate::{Keypad, KeyWait, KeyWaitMode};
+use crate::keypad::NUM_KEYS;
+use crate::Quirks;
+use crate::AudioPattern;
+use crate::Chip8Error;
+use crate::Bus;
+use crate::{rom_hash, SaveState, StateError};
+use crate::{random_seed, Xorshift};
 use std::convert::TryFrom;
+use std::fmt;
+use std::ops::Range;
 
 pub const FONTSET_BASE: usize = 0x050;
+pub const BIG_FONTSET_BASE: usize = 0x0a0;
 pub const PROGRAM_BASE: usize = 0x200;
+pub const RAM_SIZE: usize = 0x10000;
 
 const FONTSET: [u8; 5 * 16] = [
     0xF0, 0x90, 0x90, 0x90, 0xF0, // 0
@@ -26,58 +37,122 @@ const FONTSET: [u8; 5 * 16] = [
     0xF0, 0x80, 0xF0, 0x80, 0x80  // F
 ];
 
+const BIG_FONTSET: [u8; 10 * 16] = [
+    0xFF, 0xFF, 0xC3, 0xC3, 0xC3, 0xC3, 0xC3, 0xC3, 0xFF, 0xFF, // 0
+    0x18, 0x78, 0x78, 0x18, 0x18, 0x18, 0x18, 0x18, 0xFF, 0xFF, // 1
+    0xFF, 0xFF, 0x03, 0x03, 0xFF, 0xFF, 0xC0, 0xC0, 0xFF, 0xFF, // 2
+    0xFF, 0xFF, 0x03, 0x03, 0xFF, 0xFF, 0x03, 0x03, 0xFF, 0xFF, // 3
+    0xC3, 0xC3, 0xC3, 0xC3, 0xFF, 0xFF, 0x03, 0x03, 0x03, 0x03, // 4
+    0xFF, 0xFF, 0xC0, 0xC0, 0xFF, 0xFF, 0x03, 0x03, 0xFF, 0xFF, // 5
+    0xFF, 0xFF, 0xC0, 0xC0, 0xFF, 0xFF, 0xC3, 0xC3, 0xFF, 0xFF, // 6
+    0xFF, 0xFF, 0x03, 0x03, 0x06, 0x0C, 0x18, 0x18, 0x18, 0x18, // 7
+    0xFF, 0xFF, 0xC3, 0xC3, 0xFF, 0xFF, 0xC3, 0xC3, 0xFF, 0xFF, // 8
+    0xFF, 0xFF, 0xC3, 0xC3, 0xFF, 0xFF, 0x03, 0x03, 0xFF, 0xFF, // 9
+    0x7E, 0xFF, 0xC3, 0xC3, 0xC3, 0xFF, 0xFF, 0xC3, 0xC3, 0xC3, // A
+    0xFC, 0xFC, 0xC3, 0xC3, 0xFC, 0xFC, 0xC3, 0xC3, 0xFC, 0xFC, // B
+    0x3C, 0xFF, 0xC3, 0xC0, 0xC0, 0xC0, 0xC0, 0xC3, 0xFF, 0x3C, // C
+    0xFC, 0xFE, 0xC3, 0xC3, 0xC3, 0xC3, 0xC3, 0xC3, 0xFE, 0xFC, // D
+    0xFF, 0xFF, 0xC0, 0xC0, 0xFF, 0xFF, 0xC0, 0xC0, 0xFF, 0xFF, // E
+    0xFF, 0xFF, 0xC0, 0xC0, 0xFF, 0xFF, 0xC0, 0xC0, 0xC0, 0xC0  // F
+];
+
 #[derive(Debug)]
 pub struct Cpu {
     pub video: Video,
-    ram: Vec<u8>,
+    bus: Bus,
     v: [u8; 16],
     i: u16,
     pc: usize,
     stack: [usize; 16],
     sp: usize,
-    dt: u8,
-    key: u8,
-    wait_key: usize
+    timers: Timers,
+    keypad: Keypad,
+    wait_key: KeyWait,
+    key_wait_mode: KeyWaitMode,
+    quirks: Quirks,
+    vblank_wait: bool,
+    rpl: [u8; 16],
+    halted: bool,
+    audio_pattern: Option<AudioPattern>,
+    rom_hash: u64,
+    cycles: u64,
+    seed: u64,
+    rng: Xorshift,
 }
 
 
 impl Cpu {
-    pub fn new(program: &[u8]) -> Self {
+    // `RND` is seeded randomly; see `set_seed` for reproducible runs.
+    pub fn new(program: &[u8]) -> Result<Self, Chip8Error> {
+        let seed = random_seed();
         let mut cpu = Cpu {
-            ram: vec![0u8; 0xfff],
+            bus: Bus::new(RAM_SIZE),
             v: [0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0],
             i: 0,
             pc: 0,
             stack: [0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0],
             sp: 0,
-            dt: 0,
+            timers: Timers::new(),
             video: Video::new(),
-            key: 0xff,
-            wait_key: 0xff
+            keypad: Keypad::new(),
+            wait_key: KeyWait::Idle,
+            key_wait_mode: KeyWaitMode::default(),
+            quirks: Quirks::default(),
+            vblank_wait: false,
+            rpl: [0; 16],
+            halted: false,
+            audio_pattern: None,
+            rom_hash: rom_hash(program),
+            cycles: 0,
+            seed,
+            rng: Xorshift::new(seed),
         };
 
+        if program.len() > RAM_SIZE - PROGRAM_BASE {
+            return Err(Chip8Error::RomTooLarge { size: program.len(), max: RAM_SIZE - PROGRAM_BASE });
+        }
+
         cpu.load(&FONTSET, FONTSET_BASE);
-        cpu.load(&program, PROGRAM_BASE);
+        cpu.load(&BIG_FONTSET, BIG_FONTSET_BASE);
+        cpu.load(program, PROGRAM_BASE);
         cpu.pc = PROGRAM_BASE;
 
-        cpu
+        Ok(cpu)
     }
 
-    pub fn current(&mut self) -> (usize, u16, Op) {
-        let word = u16::from(self.ram[self.pc]) << 8 | u16::from(self.ram[self.pc + 1]);
-        (
-            self.pc,
-            word,
-            Cpu::parse(word)
-        )
+    #[must_use]
+    pub fn current(&self) -> (usize, u16, Op) {
+        self.bus.fetch(self.pc)
     }
 
-    #[allow(clippy::too_many_lines)]
-    pub fn step(&mut self, op: &Op) {
-        if self.dt > 0 {
-            self.dt -= 1;
+    #[must_use]
+    pub fn op_at(&self, addr: usize) -> (u16, Op) {
+        let (_, word, op) = self.bus.fetch(addr);
+        (word, op)
+    }
+
+    fn word(&self, addr: usize) -> u16 {
+        u16::from(self.bus.peek(addr)) << 8 | u16::from(self.bus.peek(addr + 1))
+    }
+
+    // Skips jump over the whole next instruction, which is two words for
+    // XO-CHIP's `F000 nnnn`.
+    fn skip(&self) -> usize {
+        if self.word(self.pc + 2) == 0xf000 { 6 } else { 4 }
+    }
+
+    // `len` bytes of RAM starting at I.
+    fn i_range(&self, len: usize) -> Result<Range<usize>, Chip8Error> {
+        let start = usize::from(self.i);
+        if start + len > RAM_SIZE {
+            return Err(Chip8Error::MemoryOutOfBounds { pc: self.pc, addr: start + len - 1 });
         }
 
+        Ok(start..start + len)
+    }
+
+    #[allow(clippy::too_many_lines)]
+    pub fn step(&mut self, op: &Op) -> Result<(), Chip8Error> {
         match *op {
             Op::LD_Vx_byte { x, nn } => {
                 self.v[x] = nn;
@@ -99,7 +174,7 @@ impl Cpu {
                     None => {
                         self.v[0xf] = 1;
                     }
-                };
+                }
 
                 self.v[x] = self.v[x].wrapping_add(self.v[y]);
                 self.pc += 2;
@@ -112,7 +187,7 @@ impl Cpu {
                     None => {
                         self.v[0xf] = 0;
                     }
-                };
+                }
 
                 self.v[x] = self.v[x].wrapping_sub(self.v[y]);
                 self.pc += 2;
@@ -125,85 +200,103 @@ impl Cpu {
                     None => {
                         self.v[0xf] = 0;
                     }
-                };
+                }
 
-                self.v[y] = self.v[y].wrapping_sub(self.v[x]);
+                self.v[x] = self.v[y].wrapping_sub(self.v[x]);
                 self.pc += 2;
             },
             Op::AND_Vx_Vy { x, y } => {
-                self.v[x] = self.v[x] & self.v[y];
+                self.v[x] &= self.v[y];
+                if self.quirks.vf_reset {
+                    self.v[0xf] = 0;
+                }
                 self.pc += 2;
             },
             Op::OR_Vx_Vy { x, y } => {
-                self.v[x] = self.v[x] | self.v[y];
+                self.v[x] |= self.v[y];
+                if self.quirks.vf_reset {
+                    self.v[0xf] = 0;
+                }
                 self.pc += 2;
             },
             Op::XOR_Vx_Vy { x, y } => {
-                self.v[x] = self.v[x] ^ self.v[y];
+                self.v[x] ^= self.v[y];
+                if self.quirks.vf_reset {
+                    self.v[0xf] = 0;
+                }
                 self.pc += 2;
             },
             Op::SHR_Vx_Vy { x, y } => {
-                self.v[0xf] = self.v[y] & 0x01;
-                self.v[x] = self.v[y] >> 1;
+                let src = if self.quirks.shift { self.v[x] } else { self.v[y] };
+                self.v[0xf] = src & 0x01;
+                self.v[x] = src >> 1;
                 self.pc += 2;
             },
             Op::SHL_Vx_Vy { x, y } => {
-                self.v[0xf] = (self.v[y] & 0x80) >> 7;
-                self.v[x] = self.v[y] << 1;
+                let src = if self.quirks.shift { self.v[x] } else { self.v[y] };
+                self.v[0xf] = (src & 0x80) >> 7;
+                self.v[x] = src << 1;
                 self.pc += 2;
             },
             Op::RND_Vx_byte { x, nn } => {
-                let rnd: u8 = rand::thread_rng().gen();
-                self.v[x] = rnd & nn;
+                self.v[x] = self.rng.next_u8() & nn;
                 self.pc += 2;
             },
             Op::JP_addr { nnn } => {
                 self.pc = nnn;
             },
             Op::JP_V0_addr { nnn } => {
-                self.pc = nnn + self.v[0] as usize;
+                let offset = if self.quirks.jump { self.v[(nnn >> 8) & 0xf] } else { self.v[0] };
+                self.pc = nnn + usize::from(offset);
             },
             Op::CALL_addr { nnn } => {
-                self.stack[self.sp] = self.pc + 2;
+                if self.sp == self.stack.len() {
+                    return Err(Chip8Error::StackOverflow { pc: self.pc });
+                }
+                self.stack[self.sp] = (self.pc + 2) % self.bus.len();
                 self.sp += 1;
                 self.pc = nnn;
             },
             Op::RET {} => {
+                if self.sp == 0 {
+                    return Err(Chip8Error::StackUnderflow { pc: self.pc });
+                }
                 self.sp -= 1;
                 self.pc = self.stack[self.sp];
             },
             Op::SE_Vx_byte { x, nn } => {
-                self.pc += if self.v[x] == nn { 4 } else { 2 }
+                self.pc += if self.v[x] == nn { self.skip() } else { 2 }
             },
             Op::SE_Vx_Vy { x, y } => {
-                self.pc += if self.v[x] == self.v[y] { 4 } else { 2 }
+                self.pc += if self.v[x] == self.v[y] { self.skip() } else { 2 }
             },
             Op::SNE_Vx_byte { x, nn } => {
-                self.pc += if self.v[x] == nn { 2 } else { 4 }
+                self.pc += if self.v[x] == nn { 2 } else { self.skip() }
             },
             Op::SNE_Vx_Vy { x, y } => {
-                self.pc += if self.v[x] == self.v[y] { 2 } else { 4 }
+                self.pc += if self.v[x] == self.v[y] { 2 } else { self.skip() }
             },
             Op::LD_DT_Vx { x } => {
-                self.dt = self.v[x];
+                self.timers.delay = self.v[x];
                 self.pc += 2;
             },
             Op::LD_Vx_DT { x } => {
-                self.v[x] = self.dt;
+                self.v[x] = self.timers.delay;
                 self.pc += 2;
             },
-            Op::LD_ST_Vx { x: _ } => {
+            Op::LD_ST_Vx { x } => {
+                self.timers.sound = self.v[x];
                 self.pc += 2;
             },
             Op::LD_Vx_K { x } => {
-                self.wait_key = x;
+                self.wait_key = KeyWait::Press { x };
                 self.pc += 2;
             },
             Op::SKP_Vx { x } => {
-                self.pc += if self.v[x] == self.key { 4 } else { 2 }
+                self.pc += if self.keypad.is_pressed(self.v[x]) { self.skip() } else { 2 }
             },
             Op::SKNP_Vx { x } => {
-                self.pc += if self.v[x] == self.key { 2 } else { 4 }
+                self.pc += if self.keypad.is_pressed(self.v[x]) { 2 } else { self.skip() }
             },
             Op::LD_I_addr { nnn } => {
                 self.i = u16::try_from(nnn).unwrap();
@@ -217,17 +310,31 @@ impl Cpu {
                     None => {
                         self.v[0xf] = 1;
                     }
-                };
+                }
 
                 self.i = self.i.wrapping_add(u16::from(self.v[x]));
                 self.pc += 2;
             },
+            Op::DRW_Vx_Vy_nibble { x, y, n: 0 } => {
+                let sprite = self.i_range(32 * self.video.plane_count())?;
+                self.v[0xf] = self.video.draw_wide(
+                    self.bus.read_slice(sprite),
+                    i32::from(self.v[x]),
+                    i32::from(self.v[y]),
+                    self.quirks.clipping
+                );
+                self.vblank_wait = self.quirks.display_wait;
+                self.pc += 2;
+            },
             Op::DRW_Vx_Vy_nibble { x, y, n } => {
+                let sprite = self.i_range(usize::from(n) * self.video.plane_count())?;
                 self.v[0xf] = self.video.draw(
-                    &self.ram[(self.i as usize)..((self.i + u16::from(n)) as usize)],
+                    self.bus.read_slice(sprite),
                     i32::from(self.v[x]),
-                    i32::from(self.v[y])
-                ) as u8;
+                    i32::from(self.v[y]),
+                    self.quirks.clipping
+                );
+                self.vblank_wait = self.quirks.display_wait;
                 self.pc += 2;
             },
             Op::CLS {} => {
@@ -239,139 +346,367 @@ impl Cpu {
                 self.pc += 2;
             },
             Op::LD_B_Vx { x } => {
-                let mut val = self.v[x];
-                for i in (0..3).rev() {
-                    self.ram[self.i as usize + i] = val % 10;
-                    val /= 10;
-                }
+                let val = self.v[x];
+                let range = self.i_range(3)?;
+                self.bus.write_slice(range.start, &[val / 100, val / 10 % 10, val % 10]);
                 self.pc += 2;
             },
             Op::LD_I_Vx { x } => {
-                for i in 0..x {
-                    self.ram[self.i as usize + i] = self.v[i];
+                let range = self.i_range(x + 1)?;
+                self.bus.write_slice(range.start, &self.v[..=x]);
+                if self.quirks.load_store {
+                    self.i = self.i.wrapping_add(u16::try_from(x + 1).unwrap());
                 }
                 self.pc += 2;
             },
             Op::LD_Vx_I { x } => {
-                for i in 0..x {
-                    self.v[i] = self.ram[self.i as usize + i];
+                let range = self.i_range(x + 1)?;
+                self.v[..=x].copy_from_slice(self.bus.read_slice(range));
+                if self.quirks.load_store {
+                    self.i = self.i.wrapping_add(u16::try_from(x + 1).unwrap());
                 }
                 self.pc += 2;
             },
+            Op::SCD_nibble { n } => {
+                self.video.scroll_down(i32::from(n));
+                self.pc += 2;
+            },
+            Op::SCU_nibble { n } => {
+                self.video.scroll_up(i32::from(n));
+                self.pc += 2;
+            },
+            Op::SCR {} => {
+                self.video.scroll_right(4);
+                self.pc += 2;
+            },
+            Op::SCL {} => {
+                self.video.scroll_left(4);
+                self.pc += 2;
+            },
+            Op::EXIT {} => {
+                self.halted = true;
+            },
+            Op::LOW {} => {
+                self.video.set_hires(false);
+                self.pc += 2;
+            },
+            Op::HIGH {} => {
+                self.video.set_hires(true);
+                self.pc += 2;
+            },
+            Op::LD_HF_Vx { x } => {
+                self.i = u16::try_from(BIG_FONTSET_BASE + usize::from(self.v[x] & 0xf) * 10).unwrap();
+                self.pc += 2;
+            },
+            Op::LD_R_Vx { x } => {
+                self.rpl[..=x].copy_from_slice(&self.v[..=x]);
+                self.pc += 2;
+            },
+            Op::LD_Vx_R { x } => {
+                self.v[..=x].copy_from_slice(&self.rpl[..=x]);
+                self.pc += 2;
+            },
+            Op::LD_I_long { nnnn } => {
+                self.i = u16::try_from(nnnn).unwrap();
+                self.pc += 4;
+            },
+            Op::SAVE_Vx_Vy { x, y } => {
+                let range = self.i_range(x.abs_diff(y) + 1)?;
+                for (addr, r) in range.zip(Cpu::register_range(x, y)) {
+                    self.bus.write(addr, self.v[r]);
+                }
+                self.pc += 2;
+            },
+            Op::LOAD_Vx_Vy { x, y } => {
+                let range = self.i_range(x.abs_diff(y) + 1)?;
+                for (addr, r) in range.zip(Cpu::register_range(x, y)) {
+                    self.v[r] = self.bus.read(addr);
+                }
+                self.pc += 2;
+            },
+            Op::PLANE_n { n } => {
+                self.video.set_planes(n);
+                self.pc += 2;
+            },
+            Op::AUDIO {} => {
+                let range = self.i_range(16)?;
+                self.audio_pattern.get_or_insert_with(AudioPattern::default)
+                    .bits.copy_from_slice(self.bus.read_slice(range));
+                self.pc += 2;
+            },
+            Op::PITCH_Vx { x } => {
+                self.audio_pattern.get_or_insert_with(AudioPattern::default).pitch = self.v[x];
+                self.pc += 2;
+            },
             Op::UNKNOWN {} => {
-                panic!("UNKNOWN op")
+                return Err(Chip8Error::UnknownOpcode { pc: self.pc, word: self.word(self.pc) });
             },
         }
-    }
 
-    pub fn disassemble(data: &[u8]) {
-        let list = data.chunks(2)
-            .map(|x| u16::from(x[0]) << 8 | u16::from(x[1]))
-            .collect::<Vec<_>>();
+        self.wrap_pc();
+        self.cycles += 1;
+        Ok(())
+    }
 
-        for (i, word) in list.iter().enumerate() {
-            println!("{:#06x} {:#06x} {}", PROGRAM_BASE + i * 2, word, Cpu::parse(*word));
+    // Jumps and skips near the top of memory carry on from address 0, the
+    // way fetches already do.
+    fn wrap_pc(&mut self) {
+        if self.pc >= self.bus.len() {
+            self.pc %= self.bus.len();
         }
     }
 
-    pub fn waiting_key(&mut self) -> bool {
-        self.wait_key != 0xff
+    // Finishes an instruction executed outside `step`: `len` bytes on, one
+    // more cycle.
+    #[cfg(feature = "dynarec")]
+    pub(crate) fn retire(&mut self, len: usize) {
+        self.pc += len;
+        self.wrap_pc();
+        self.cycles += 1;
+    }
+
+    #[must_use]
+    pub fn bus(&self) -> &Bus {
+        &self.bus
+    }
+
+    pub fn bus_mut(&mut self) -> &mut Bus {
+        &mut self.bus
+    }
+
+    #[must_use]
+    pub fn pc(&self) -> usize {
+        self.pc
+    }
+
+    pub fn set_pc(&mut self, pc: usize) {
+        self.pc = pc % self.bus.len();
+    }
+
+    #[must_use]
+    pub fn i(&self) -> u16 {
+        self.i
+    }
+
+    pub fn set_i(&mut self, i: u16) {
+        self.i = i;
+    }
+
+    #[must_use]
+    pub fn v(&self) -> &[u8; 16] {
+        &self.v
+    }
+
+    pub fn v_mut(&mut self) -> &mut [u8; 16] {
+        &mut self.v
+    }
+
+    #[must_use]
+    pub fn sp(&self) -> usize {
+        self.sp
+    }
+
+    // Clamped to the stack depth.
+    pub fn set_sp(&mut self, sp: usize) {
+        self.sp = sp.min(self.stack.len());
+    }
+
+    // Return addresses pushed by `CALL`, oldest first.
+    #[must_use]
+    pub fn stack(&self) -> &[usize] {
+        &self.stack[..self.sp]
+    }
+
+    pub fn tick_timers(&mut self) {
+        self.timers.tick();
+        self.vblank_wait = false;
+    }
+
+    #[must_use]
+    pub fn timers(&self) -> &Timers {
+        &self.timers
+    }
+
+    pub fn timers_mut(&mut self) -> &mut Timers {
+        &mut self.timers
+    }
+
+    #[must_use]
+    pub fn waiting_key(&self) -> bool {
+        self.wait_key.waiting()
+    }
+
+    // True while blocked on `LD Vx, K` or, with the display wait quirk, until
+    // the next frame after a draw.
+    #[must_use]
+    pub fn waiting(&self) -> bool {
+        self.waiting_key() || self.vblank_wait
+    }
+
+    #[must_use]
+    pub fn quirks(&self) -> &Quirks {
+        &self.quirks
+    }
+
+    pub fn set_quirks(&mut self, quirks: Quirks) {
+        self.quirks = quirks;
+    }
+
+    // The XO-CHIP pattern loaded by `AUDIO`/`PITCH`, if the program set one.
+    #[must_use]
+    pub fn audio_pattern(&self) -> Option<&AudioPattern> {
+        self.audio_pattern.as_ref()
+    }
+
+    // Set once the program executes `EXIT`.
+    #[must_use]
+    pub fn halted(&self) -> bool {
+        self.halted
+    }
+
+    #[must_use]
+    pub fn key_wait_mode(&self) -> KeyWaitMode {
+        self.key_wait_mode
+    }
+
+    pub fn set_key_wait_mode(&mut self, mode: KeyWaitMode) {
+        self.key_wait_mode = mode;
     }
 
     pub fn press(&mut self, key: u8) {
-        if self.wait_key != 0xff {
-            self.v[self.wait_key] = key;
-            self.wait_key = 0xff;
+        if usize::from(key) >= NUM_KEYS || self.keypad.is_pressed(key) {
+            return;
         }
 
-        self.key = key;
+        if let Some((x, key)) = self.wait_key.press(key, self.key_wait_mode) {
+            self.v[x] = key;
+        }
+
+        self.keypad.press(key);
     }
 
-    pub fn release(&mut self) {
-        self.key = 0xff;
+    pub fn release(&mut self, key: u8) {
+        if !self.keypad.is_pressed(key) {
+            return;
+        }
+
+        if let Some((x, key)) = self.wait_key.release(key) {
+            self.v[x] = key;
+        }
+
+        self.keypad.release(key);
     }
 
-    fn load(&mut self, data: &[u8], base: usize) {
-        self.ram[base..base + (data.len() as usize)].copy_from_slice(&data);
-    }
-
-    fn parse(op: u16) -> Op {
-        let x =  ((op & 0x0f00) >> 8) as usize;
-        let y =  ((op & 0x00f0) >> 4) as usize;
-        let n =   (op & 0x000f) as u8;
-        let nn =  (op & 0x00ff) as u8;
-        let nnn = (op & 0x0fff) as usize;
-
-        if op & 0xf000 == 0x6000 {
-            Op::LD_Vx_byte { x, nn }
-        } else if op & 0xf000 == 0x7000 {
-            Op::ADD_Vx_byte { x, nn }
-        } else if op & 0xf00f == 0x8000 {
-            Op::LD_Vx_Vy { x, y }
-        } else if op & 0xf00f == 0x8004 {
-            Op::ADD_Vx_Vy { x, y }
-        } else if op & 0xf00f == 0x8005 {
-            Op::SUB_Vx_Vy { x, y }
-        } else if op & 0xf00f == 0x8007 {
-            Op::SUBN_Vx_Vy { x, y }
-        } else if op & 0xf00f == 0x8002 {
-            Op::AND_Vx_Vy { x, y }
-        } else if op & 0xf00f == 0x8001 {
-            Op::OR_Vx_Vy { x, y }
-        } else if op & 0xf00f == 0x8003 {
-            Op::XOR_Vx_Vy { x, y }
-        } else if op & 0xf00f == 0x8006 {
-            Op::SHR_Vx_Vy { x, y }
-        } else if op & 0xf00f == 0x800e {
-            Op::SHL_Vx_Vy { x, y }
-        } else if op & 0xf000 == 0xc000 {
-            Op::RND_Vx_byte { x, nn }
-        } else if op & 0xf000 == 0x1000 {
-            Op::JP_addr { nnn }
-        } else if op & 0xf000 == 0xb000 {
-            Op::JP_V0_addr { nnn }
-        } else if op & 0xf000 == 0x2000 {
-            Op::CALL_addr { nnn }
-        } else if op == 0x00ee {
-            Op::RET {}
-        } else if op & 0xf000 == 0x3000 {
-            Op::SE_Vx_byte { x, nn }
-        } else if op & 0xf00f == 0x5000 {
-            Op::SE_Vx_Vy { x, y }
-        } else if op & 0xf000 == 0x4000 {
-            Op::SNE_Vx_byte { x, nn }
-        } else if op & 0xf00f == 0x9000 {
-            Op::SNE_Vx_Vy { x, y }
-        } else if op & 0xf0ff == 0xf015 {
-            Op::LD_DT_Vx { x }
-        } else if op & 0xf0ff == 0xf007 {
-            Op::LD_Vx_DT { x }
-        } else if op & 0xf0ff == 0xf018 {
-            Op::LD_ST_Vx { x }
-        } else if op & 0xf0ff == 0xf00a {
-            Op::LD_Vx_K { x }
-        } else if op & 0xf0ff == 0xe09e {
-            Op::SKP_Vx { x }
-        } else if op & 0xf0ff == 0xe0a1 {
-            Op::SKNP_Vx { x }
-        } else if op & 0xf000 == 0xa000 {
-            Op::LD_I_addr { nnn }
-        } else if op & 0xf0ff == 0xf01e {
-            Op::ADD_I_Vx { x }
-        } else if op & 0xf000 == 0xd000 {
-            Op::DRW_Vx_Vy_nibble { x, y, n }
-        } else if op & 0xffff == 0x00e0 {
-            Op::CLS {}
-        } else if op & 0xf0ff == 0xf029 {
-            Op::LD_F_Vx { x }
-        } else if op & 0xf0ff == 0xf033 {
-            Op::LD_B_Vx { x }
-        } else if op & 0xf0ff == 0xf055 {
-            Op::LD_I_Vx { x }
-        } else if op & 0xf0ff == 0xf065 {
-            Op::LD_Vx_I { x }
+    #[must_use]
+    pub fn keypad(&self) -> &Keypad {
+        &self.keypad
+    }
+
+    // FNV-1a hash of the ROM this CPU was created with.
+    #[must_use]
+    pub fn rom_hash(&self) -> u64 {
+        self.rom_hash
+    }
+
+    #[must_use]
+    pub fn seed(&self) -> u64 {
+        self.seed
+    }
+
+    // Restarts the `RND` sequence from `seed`.
+    pub fn set_seed(&mut self, seed: u64) {
+        self.seed = seed;
+        self.rng = Xorshift::new(seed);
+    }
+
+    // Instructions executed so far.
+    #[must_use]
+    pub fn cycles(&self) -> u64 {
+        self.cycles
+    }
+
+    #[must_use]
+    pub fn save_state(&self) -> SaveState {
+        SaveState {
+            rom_hash: self.rom_hash,
+            quirks: self.quirks,
+            ram: self.bus.peek_slice(0..self.bus.len()).to_vec(),
+            video: self.video.clone(),
+            v: self.v,
+            i: self.i,
+            pc: self.pc,
+            stack: self.stack,
+            sp: self.sp,
+            timers: self.timers,
+            keypad: self.keypad,
+            wait_key: self.wait_key,
+            key_wait_mode: self.key_wait_mode,
+            vblank_wait: self.vblank_wait,
+            rpl: self.rpl,
+            halted: self.halted,
+            audio_pattern: self.audio_pattern,
+            cycles: self.cycles,
+            rng: self.rng.state(),
+        }
+    }
+
+    // Refuses states taken from a different ROM, leaving the CPU untouched.
+    pub fn load_state(&mut self, state: &SaveState) -> Result<(), StateError> {
+        if state.rom_hash != self.rom_hash {
+            return Err(StateError::RomMismatch { expected: self.rom_hash, found: state.rom_hash });
+        }
+        if state.ram.len() != self.bus.len() {
+            return Err(StateError::Invalid("memory size"));
+        }
+        if state.pc >= self.bus.len() || state.stack.iter().any(|addr| *addr >= self.bus.len()) {
+            return Err(StateError::Invalid("address"));
+        }
+
+        self.bus.load(0, &state.ram);
+        self.video = state.video.clone();
+        self.quirks = state.quirks;
+        self.v = state.v;
+        self.i = state.i;
+        self.pc = state.pc;
+        self.stack = state.stack;
+        self.sp = state.sp;
+        self.timers = state.timers;
+        self.keypad = state.keypad;
+        self.wait_key = state.wait_key;
+        self.key_wait_mode = state.key_wait_mode;
+        self.vblank_wait = state.vblank_wait;
+        self.rpl = state.rpl;
+        self.halted = state.halted;
+        self.audio_pattern = state.audio_pattern;
+        self.cycles = state.cycles;
+        self.rng = Xorshift::from_state(state.rng);
+
+        Ok(())
+    }
+
+    // `SAVE`/`LOAD` walk from Vx to Vy, backwards when x > y.
+    fn register_range(x: usize, y: usize) -> Box<dyn Iterator<Item = usize>> {
+        if x <= y {
+            Box::new(x..=y)
         } else {
-            Op::UNKNOWN {}
+            Box::new((y..=x).rev())
         }
     }
+
+    fn load(&mut self, data: &[u8], base: usize) {
+        self.bus.load(base, data);
+    }
+}
+
+impl fmt::Display for Cpu {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        writeln!(f, "PC {:#06x}  I {:#06x}  SP {:x}  DT {:02x}  ST {:02x}",
+            self.pc, self.i, self.sp, self.timers.delay, self.timers.sound)?;
+        for (n, v) in self.v.iter().enumerate() {
+            let sep = if n % 8 == 7 { "\n" } else { "  " };
+            write!(f, "V{n:X} {v:02x}{sep}")?;
+        }
+
+        Ok(())
+    }
 }
@@ -0,0 +1,394 @@
+use std::collections::BTreeSet;
+use std::fmt;
+use std::ops::RangeInclusive;
+use std::str::FromStr;
+
+use crate::{AccessKind, Chip8Error, Cpu, Op, Scheduler};
+
+use log::debug;
+
+// Why the debugger paused the program.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Break {
+    Breakpoint(usize),
+    // Index into `Debugger::watches` and the address of the instruction,
+    // or the frame's timer tick, that triggered it.
+    Watchpoint { index: usize, pc: usize },
+    Step,
+    Return,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum WatchKind {
+    Read,
+    Write,
+    Access,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Register {
+    V(usize),
+    I,
+    Dt,
+}
+
+impl Register {
+    fn value(self, cpu: &Cpu) -> u16 {
+        match self {
+            Register::V(x) => u16::from(cpu.v()[x]),
+            Register::I => cpu.i(),
+            Register::Dt => u16::from(cpu.timers().delay),
+        }
+    }
+}
+
+// Tested against a register's new value whenever it changes.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Condition {
+    Any,
+    Equals(u16),
+    NotEquals(u16),
+    Less(u16),
+    Greater(u16),
+}
+
+impl Condition {
+    fn matches(self, value: u16) -> bool {
+        match self {
+            Condition::Any => true,
+            Condition::Equals(v) => value == v,
+            Condition::NotEquals(v) => value != v,
+            Condition::Less(v) => value < v,
+            Condition::Greater(v) => value > v,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Watch {
+    Memory { range: RangeInclusive<usize>, kind: WatchKind },
+    Register { register: Register, condition: Condition },
+}
+
+impl Watch {
+    fn memory_hit(&self, addr: usize, access: AccessKind) -> bool {
+        match (self, access) {
+            (Watch::Memory { range, kind: WatchKind::Read | WatchKind::Access }, AccessKind::Read)
+            | (Watch::Memory { range, kind: WatchKind::Write | WatchKind::Access }, AccessKind::Write) => range.contains(&addr),
+            _ => false,
+        }
+    }
+}
+
+fn parse_number(s: &str) -> Result<usize, String> {
+    let s = s.trim();
+    let parsed = match s.strip_prefix("0x") {
+        Some(hex) => usize::from_str_radix(hex, 16),
+        None => s.parse(),
+    };
+    parsed.map_err(|e| format!("{s}: {e}"))
+}
+
+// `r:0x300`, `w:0x300-0x30f` or `rw:...` watch memory; `v3`, `i` or `dt`
+// watch a register for any change, `v3==0x10`, `dt!=0`, `i<0x400` or `v0>8`
+// for a change to a matching value.
+impl FromStr for Watch {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        if let Some((kind, range)) = s.split_once(':') {
+            let kind = match kind {
+                "r" => WatchKind::Read,
+                "w" => WatchKind::Write,
+                "rw" => WatchKind::Access,
+                _ => return Err(format!("unknown watch kind {kind}, expected r|w|rw")),
+            };
+            let (start, end) = range.split_once('-').unwrap_or((range, range));
+            return Ok(Watch::Memory { range: parse_number(start)?..=parse_number(end)?, kind });
+        }
+
+        let at = s.find(['=', '!', '<', '>']).unwrap_or(s.len());
+        let (register, condition) = s.split_at(at);
+        let register = match register.to_ascii_lowercase().as_str() {
+            "i" => Register::I,
+            "dt" => Register::Dt,
+            v => match v.strip_prefix('v').map(|x| usize::from_str_radix(x, 16)) {
+                Some(Ok(x)) if x < 16 => Register::V(x),
+                _ => return Err(format!("unknown register {register}, expected v0-vf|i|dt")),
+            },
+        };
+
+        let value = |rest: &str| parse_number(rest).and_then(|v| u16::try_from(v).map_err(|e| e.to_string()));
+        let condition = if condition.is_empty() {
+            Condition::Any
+        } else if let Some(rest) = condition.strip_prefix("==") {
+            Condition::Equals(value(rest)?)
+        } else if let Some(rest) = condition.strip_prefix("!=") {
+            Condition::NotEquals(value(rest)?)
+        } else if let Some(rest) = condition.strip_prefix('<') {
+            Condition::Less(value(rest)?)
+        } else if let Some(rest) = condition.strip_prefix('>') {
+            Condition::Greater(value(rest)?)
+        } else {
+            return Err(format!("unknown condition {condition}, expected ==|!=|<|>"));
+        };
+
+        Ok(Watch::Register { register, condition })
+    }
+}
+
+impl fmt::Display for Watch {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Watch::Memory { range, kind } => {
+                let kind = match kind {
+                    WatchKind::Read => "r",
+                    WatchKind::Write => "w",
+                    WatchKind::Access => "rw",
+                };
+                write!(f, "{kind}:{:#06x}-{:#06x}", range.start(), range.end())
+            }
+            Watch::Register { register, condition } => {
+                match register {
+                    Register::V(x) => write!(f, "v{x:x}")?,
+                    Register::I => write!(f, "i")?,
+                    Register::Dt => write!(f, "dt")?,
+                }
+                match condition {
+                    Condition::Any => Ok(()),
+                    Condition::Equals(v) => write!(f, "=={v:#x}"),
+                    Condition::NotEquals(v) => write!(f, "!={v:#x}"),
+                    Condition::Less(v) => write!(f, "<{v:#x}"),
+                    Condition::Greater(v) => write!(f, ">{v:#x}"),
+                }
+            }
+        }
+    }
+}
+
+// Where a resumed run should stop on its own.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Target {
+    // Back at `pc` with the stack at `sp`, i.e. after a stepped-over `CALL`.
+    Until { pc: usize, sp: usize },
+    // The stack dropped below `sp`, i.e. the current subroutine returned.
+    Return { sp: usize },
+    // An instruction past `cycles` ran, i.e. a step that had to wait.
+    Step { cycles: u64 },
+}
+
+// Wraps the scheduler with pause/resume, stepping and PC breakpoints.
+#[derive(Debug, Default)]
+pub struct Debugger {
+    breakpoints: BTreeSet<usize>,
+    watches: Vec<Watch>,
+    paused: bool,
+    target: Option<Target>,
+    // Lets a run resume from the breakpoint it stopped on.
+    resume_from: Option<usize>,
+    last_break: Option<Break>,
+}
+
+impl Debugger {
+    #[must_use]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    #[must_use]
+    pub fn paused(&self) -> bool {
+        self.paused
+    }
+
+    #[must_use]
+    pub fn last_break(&self) -> Option<Break> {
+        self.last_break
+    }
+
+    pub fn pause(&mut self) {
+        self.paused = true;
+        self.target = None;
+    }
+
+    pub fn resume(&mut self, cpu: &Cpu) {
+        self.paused = false;
+        self.resume_from = Some(cpu.pc());
+    }
+
+    #[must_use]
+    pub fn breakpoints(&self) -> &BTreeSet<usize> {
+        &self.breakpoints
+    }
+
+    pub fn add_breakpoint(&mut self, addr: usize) {
+        self.breakpoints.insert(addr);
+    }
+
+    pub fn remove_breakpoint(&mut self, addr: usize) {
+        self.breakpoints.remove(&addr);
+    }
+
+    pub fn toggle_breakpoint(&mut self, addr: usize) {
+        if !self.breakpoints.remove(&addr) {
+            self.breakpoints.insert(addr);
+        }
+    }
+
+    #[must_use]
+    pub fn watches(&self) -> &[Watch] {
+        &self.watches
+    }
+
+    pub fn add_watch(&mut self, watch: Watch) -> usize {
+        self.watches.push(watch);
+        self.watches.len() - 1
+    }
+
+    pub fn remove_watch(&mut self, index: usize) {
+        if index < self.watches.len() {
+            self.watches.remove(index);
+        }
+    }
+
+    fn registers(&self, cpu: &Cpu) -> Vec<u16> {
+        self.watches.iter()
+            .map(|watch| match watch {
+                Watch::Register { register, .. } => register.value(cpu),
+                Watch::Memory { .. } => 0,
+            })
+            .collect()
+    }
+
+    // Index of the first watch triggered since `before` was taken, checking
+    // the bus accesses recorded meanwhile.
+    fn triggered(&self, cpu: &mut Cpu, before: &[u16]) -> Option<usize> {
+        let accesses = cpu.bus_mut().take_accesses();
+        self.watches.iter().zip(before).position(|(watch, old)| match watch {
+            Watch::Memory { .. } => accesses.iter().any(|a| watch.memory_hit(a.addr, a.kind)),
+            Watch::Register { register, condition } => {
+                let new = register.value(cpu);
+                new != *old && condition.matches(new)
+            }
+        })
+    }
+
+    // Executes exactly one instruction and stays paused. A draw still
+    // waiting for the vertical blank gets it first, timers ticking as at the
+    // end of a frame. False if nothing ran: the CPU is halted or waits for a
+    // key, which only input can end.
+    pub fn step(&mut self, cpu: &mut Cpu) -> Result<bool, Chip8Error> {
+        self.pause();
+        if cpu.waiting() && !cpu.waiting_key() {
+            cpu.tick_timers();
+        }
+        if cpu.waiting() || cpu.halted() {
+            return Ok(false);
+        }
+
+        let (_, _, op) = cpu.current();
+        cpu.step(&op)?;
+        self.last_break = Some(Break::Step);
+        Ok(true)
+    }
+
+    // Runs a `CALL` through to its return, otherwise behaves like `step`.
+    pub fn step_over(&mut self, cpu: &mut Cpu) -> Result<bool, Chip8Error> {
+        match cpu.current() {
+            (pc, _, Op::CALL_addr { .. }) if !cpu.waiting_key() && !cpu.halted() => {
+                self.resume(cpu);
+                self.target = Some(Target::Until { pc: pc + 2, sp: cpu.sp() });
+                Ok(true)
+            }
+            _ => self.step(cpu),
+        }
+    }
+
+    // Runs until one more instruction has executed, for a step that `step`
+    // could not take because the CPU waits for a key.
+    pub fn step_after_wait(&mut self, cpu: &Cpu) {
+        self.resume(cpu);
+        self.target = Some(Target::Step { cycles: cpu.cycles() });
+    }
+
+    // Runs until the current subroutine returns.
+    pub fn run_to_return(&mut self, cpu: &Cpu) {
+        self.resume(cpu);
+        self.target = Some(Target::Return { sp: cpu.sp() });
+    }
+
+    // Runs one frame unless paused, stopping early on a breakpoint or once a
+    // step-over or run-to-return target is reached.
+    pub fn frame(&mut self, scheduler: &mut Scheduler, cpu: &mut Cpu) -> Result<Option<Break>, Chip8Error> {
+        if self.paused {
+            return Ok(None);
+        }
+
+        let watching = !self.watches.is_empty();
+        cpu.bus_mut().set_tracing(self.watches.iter().any(|w| matches!(w, Watch::Memory { .. })));
+
+        for _ in 0..scheduler.instructions_per_frame() {
+            if cpu.waiting() || cpu.halted() {
+                break;
+            }
+
+            let (pc, word, op) = cpu.current();
+            if self.resume_from.take() != Some(pc) && self.breakpoints.contains(&pc) {
+                return Ok(Some(self.stop(Break::Breakpoint(pc))));
+            }
+
+            debug!("{pc:#06x} {word:#06x} {op}");
+            let before = if watching { self.registers(cpu) } else { Vec::new() };
+            cpu.step(&op)?;
+
+            if watching {
+                if let Some(index) = self.triggered(cpu, &before) {
+                    return Ok(Some(self.stop(Break::Watchpoint { index, pc })));
+                }
+            }
+
+            match self.target {
+                Some(Target::Until { pc, sp }) if cpu.pc() == pc && cpu.sp() == sp => {
+                    return Ok(Some(self.stop(Break::Step)));
+                }
+                Some(Target::Return { sp }) if cpu.sp() < sp => {
+                    return Ok(Some(self.stop(Break::Return)));
+                }
+                Some(Target::Step { cycles }) if cpu.cycles() > cycles => {
+                    return Ok(Some(self.stop(Break::Step)));
+                }
+                _ => {}
+            }
+        }
+
+        let before = if watching { self.registers(cpu) } else { Vec::new() };
+        cpu.tick_timers();
+
+        if watching {
+            if let Some(index) = self.triggered(cpu, &before) {
+                return Ok(Some(self.stop(Break::Watchpoint { index, pc: cpu.pc() })));
+            }
+        }
+
+        Ok(None)
+    }
+
+    fn stop(&mut self, reason: Break) -> Break {
+        self.pause();
+        self.last_break = Some(reason);
+        reason
+    }
+
+    // Decodes `before` instructions ahead of PC through `after` past it.
+    // Linear decoding, so data mixed in with code shows up as instructions.
+    #[must_use]
+    pub fn disassembly(cpu: &Cpu, before: usize, after: usize) -> Vec<(usize, u16, Op)> {
+        let start = cpu.pc().saturating_sub(before * 2);
+        (0..=before + after)
+            .map(|n| start + n * 2)
+            .map(|addr| {
+                let (word, op) = cpu.op_at(addr);
+                (addr, word, op)
+            })
+            .collect()
+    }
+}
@@ -0,0 +1,162 @@
+use std::collections::BTreeMap;
+use std::fmt;
+
+use crate::{Chip8Error, Op, PROGRAM_BASE, RAM_SIZE};
+
+// Kinds of label, in order of precedence when an address is several.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+enum Label {
+    Data,
+    Code,
+    Sub,
+}
+
+// A ROM split into code and data by following `JP`, `CALL` and skips from
+// `PROGRAM_BASE`. Displays as a listing the assembler turns back into the
+// same bytes: code with labels for jump, call and `LD I` targets, anything
+// unreached as `db` bytes with a bitmap preview.
+#[derive(Debug)]
+pub struct Disassembly {
+    rom: Vec<u8>,
+    // Instructions by address, with their length in bytes.
+    code: BTreeMap<usize, (Op, usize)>,
+    labels: BTreeMap<usize, Label>,
+}
+
+impl Disassembly {
+    pub fn new(rom: &[u8]) -> Result<Self, Chip8Error> {
+        if rom.len() > RAM_SIZE - PROGRAM_BASE {
+            return Err(Chip8Error::RomTooLarge { size: rom.len(), max: RAM_SIZE - PROGRAM_BASE });
+        }
+        let mut this = Self { rom: rom.to_vec(), code: BTreeMap::new(), labels: BTreeMap::new() };
+
+        let mut pending = vec![PROGRAM_BASE];
+        while let Some(addr) = pending.pop() {
+            if this.code.contains_key(&addr) || !this.contains(addr) {
+                continue;
+            }
+
+            let op = this.op_at(addr);
+            let len = op.size();
+            if addr + len > this.end() || matches!(op, Op::UNKNOWN {}) {
+                continue;
+            }
+            this.code.insert(addr, (op, len));
+
+            let next = addr + len;
+            match op {
+                Op::JP_addr { nnn } | Op::JP_V0_addr { nnn } => {
+                    this.label(nnn, Label::Code);
+                    pending.push(nnn);
+                }
+                Op::CALL_addr { nnn } => {
+                    this.label(nnn, Label::Sub);
+                    pending.extend([next, nnn]);
+                }
+                Op::RET {} | Op::EXIT {} => {}
+                Op::SE_Vx_byte { .. } | Op::SE_Vx_Vy { .. } | Op::SNE_Vx_byte { .. } | Op::SNE_Vx_Vy { .. }
+                | Op::SKP_Vx { .. } | Op::SKNP_Vx { .. } => {
+                    let skipped = this.op_at(next).size();
+                    pending.extend([next + skipped, next]);
+                }
+                Op::LD_I_addr { nnn: addr } | Op::LD_I_long { nnnn: addr } => {
+                    this.label(addr, Label::Data);
+                    pending.push(next);
+                }
+                _ => pending.push(next),
+            }
+        }
+
+        Ok(this)
+    }
+
+    #[must_use]
+    pub fn is_code(&self, addr: usize) -> bool {
+        self.code.contains_key(&addr)
+    }
+
+    // Decodes straight from the ROM, reading zeroes past its end.
+    fn op_at(&self, addr: usize) -> Op {
+        let byte = |addr: usize| self.rom.get(addr - PROGRAM_BASE).copied().unwrap_or(0);
+        let word = |addr: usize| u16::from(byte(addr)) << 8 | u16::from(byte(addr + 1));
+        match Op::decode(word(addr)) {
+            Op::LD_I_long { .. } => Op::LD_I_long { nnnn: usize::from(word(addr + 2)) },
+            op => op,
+        }
+    }
+
+    fn end(&self) -> usize {
+        PROGRAM_BASE + self.rom.len()
+    }
+
+    fn contains(&self, addr: usize) -> bool {
+        (PROGRAM_BASE..self.end()).contains(&addr)
+    }
+
+    fn label(&mut self, addr: usize, kind: Label) {
+        if self.contains(addr) {
+            let label = self.labels.entry(addr).or_insert(kind);
+            *label = (*label).max(kind);
+        }
+    }
+
+    fn target(&self, addr: usize) -> String {
+        match self.labels.get(&addr) {
+            Some(Label::Sub)  => format!("sub_{addr:03x}"),
+            Some(Label::Code) => format!("label_{addr:03x}"),
+            Some(Label::Data) => format!("data_{addr:03x}"),
+            None              => format!("{addr:#05x}"),
+        }
+    }
+
+    fn instruction(&self, op: &Op) -> String {
+        match *op {
+            Op::JP_addr { nnn }    => format!("JP {}", self.target(nnn)),
+            Op::JP_V0_addr { nnn } => format!("JP V0, {}", self.target(nnn)),
+            Op::CALL_addr { nnn }  => format!("CALL {}", self.target(nnn)),
+            Op::LD_I_addr { nnn }  => format!("LD I, {}", self.target(nnn)),
+            Op::LD_I_long { nnnn } => format!("LD I, LONG {}", self.target(nnnn)),
+            _                      => op.to_string(),
+        }
+    }
+
+    // Start of every line of the listing. Code found at an address inside an
+    // earlier instruction, as with a jump into the middle of one, is left out.
+    fn lines(&self) -> Vec<usize> {
+        let mut lines = Vec::new();
+        let mut addr = PROGRAM_BASE;
+        while addr < self.end() {
+            lines.push(addr);
+            addr += self.code.get(&addr).map_or(1, |(_, len)| *len);
+        }
+        lines
+    }
+}
+
+impl fmt::Display for Disassembly {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let lines = self.lines();
+
+        // Labels that fall inside an instruction cannot be placed in the
+        // listing and become constants instead.
+        for addr in self.labels.keys().filter(|addr| lines.binary_search(addr).is_err()) {
+            writeln!(f, "{} = {addr:#05x}", self.target(*addr))?;
+        }
+
+        for addr in lines {
+            if self.labels.contains_key(&addr) {
+                writeln!(f, "{}:", self.target(addr))?;
+            }
+
+            if let Some((op, _)) = self.code.get(&addr) {
+                writeln!(f, "    {:<24}; {addr:03x}", self.instruction(op))?;
+            } else {
+                let byte = self.rom[addr - PROGRAM_BASE];
+                let bitmap = format!("{byte:08b}").replace('0', ".").replace('1', "#");
+                writeln!(f, "    {:<24}; {addr:03x} {bitmap}", format!("db {byte:#04x}"))?;
+            }
+        }
+
+        Ok(())
+    }
+}
@@ -0,0 +1,211 @@
+use std::fmt;
+
+use crate::{Chip8Error, Cpu, Op, Quirks, Scheduler};
+
+// Instructions compiled into one block at most.
+pub const MAX_BLOCK_LEN: usize = 64;
+
+type Compiled = Box<dyn Fn(&mut Cpu) -> Result<(), Chip8Error>>;
+
+// Straight-line code starting at one address, with the bytes it was compiled
+// from and the bus generation they were last seen at.
+struct Block {
+    bytes: Vec<u8>,
+    generation: u64,
+    ops: Vec<Compiled>,
+}
+
+impl Block {
+    fn compile(cpu: &Cpu, start: usize) -> Self {
+        let mut ops = Vec::new();
+        let mut addr = start;
+        loop {
+            let (_, op) = cpu.op_at(addr);
+            ops.push(translate(op, *cpu.quirks()));
+            addr += op.size();
+            if ends_block(op) || ops.len() == MAX_BLOCK_LEN {
+                break;
+            }
+        }
+
+        Self {
+            bytes: (start..addr).map(|addr| cpu.bus().peek(addr)).collect(),
+            generation: cpu.bus().generation(),
+            ops,
+        }
+    }
+
+    fn matches(&self, cpu: &Cpu, start: usize) -> bool {
+        self.bytes.iter().zip(start..).all(|(byte, addr)| cpu.bus().peek(addr) == *byte)
+    }
+}
+
+// Runs a `Cpu` by compiling basic blocks into chains of closures, with
+// operands and quirks bound in, instead of stepping one decoded instruction
+// at a time. Arithmetic runs natively; everything else goes through
+// `Cpu::step`, so the two engines end up in the same state.
+//
+// A block ends at anything that branches, waits, halts or writes memory,
+// so writes only happen between blocks. Blocks are checked against RAM when
+// the bus has been written since they were last entered, and all of them are
+// dropped when the quirks change.
+#[derive(Default)]
+pub struct Dynarec {
+    blocks: Vec<Option<Box<Block>>>,
+    quirks: Option<Quirks>,
+}
+
+impl fmt::Debug for Dynarec {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Dynarec").field("blocks", &self.blocks()).field("quirks", &self.quirks).finish()
+    }
+}
+
+impl Dynarec {
+    #[must_use]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    // Compiled blocks currently held.
+    #[must_use]
+    pub fn blocks(&self) -> usize {
+        self.blocks.iter().flatten().count()
+    }
+
+    pub fn clear(&mut self) {
+        self.blocks.clear();
+    }
+
+    // Executes up to `budget` instructions, stopping early like
+    // `Scheduler::frame` when the CPU waits or halts. Returns how many ran.
+    pub fn run(&mut self, cpu: &mut Cpu, budget: u32) -> Result<u32, Chip8Error> {
+        if self.quirks != Some(*cpu.quirks()) {
+            self.blocks.clear();
+            self.quirks = Some(*cpu.quirks());
+        }
+
+        let budget = usize::try_from(budget).unwrap();
+        let mut done = 0;
+        while done < budget && !cpu.waiting() && !cpu.halted() {
+            let block = self.block(cpu);
+            let len = block.ops.len().min(budget - done);
+            for op in &block.ops[..len] {
+                op(cpu)?;
+            }
+            done += len;
+        }
+
+        Ok(u32::try_from(done).unwrap())
+    }
+
+    // `Scheduler::frame` on compiled code.
+    pub fn frame(&mut self, scheduler: &mut Scheduler, cpu: &mut Cpu) -> Result<(), Chip8Error> {
+        self.run(cpu, scheduler.instructions_per_frame())?;
+        cpu.tick_timers();
+        Ok(())
+    }
+
+    // Blocks are kept per wrapped address: they only ever move the PC
+    // relative to where they started, like `step` does.
+    fn block(&mut self, cpu: &Cpu) -> &Block {
+        let len = cpu.bus().len();
+        if self.blocks.len() != len {
+            self.blocks.clear();
+            self.blocks.resize_with(len, || None);
+        }
+
+        let start = cpu.pc() % len;
+        let generation = cpu.bus().generation();
+        let slot = &mut self.blocks[start];
+        match slot {
+            Some(block) if block.generation == generation => {},
+            Some(block) if block.matches(cpu, start) => block.generation = generation,
+            _ => *slot = Some(Box::new(Block::compile(cpu, start))),
+        }
+
+        slot.as_ref().unwrap()
+    }
+}
+
+fn ends_block(op: Op) -> bool {
+    matches!(op,
+        Op::JP_addr { .. } | Op::JP_V0_addr { .. } | Op::CALL_addr { .. } | Op::RET {}
+            | Op::SE_Vx_byte { .. } | Op::SE_Vx_Vy { .. } | Op::SNE_Vx_byte { .. } | Op::SNE_Vx_Vy { .. }
+            | Op::SKP_Vx { .. } | Op::SKNP_Vx { .. }
+            | Op::DRW_Vx_Vy_nibble { .. } | Op::LD_Vx_K { .. } | Op::EXIT {} | Op::UNKNOWN {}
+            | Op::LD_B_Vx { .. } | Op::LD_I_Vx { .. } | Op::SAVE_Vx_Vy { .. }
+    )
+}
+
+// A two-byte instruction that only touches V0-VF.
+fn registers(f: impl Fn(&mut [u8; 16]) + 'static) -> Compiled {
+    Box::new(move |cpu| {
+        f(cpu.v_mut());
+        cpu.retire(2);
+        Ok(())
+    })
+}
+
+fn load_i(i: usize, len: usize) -> Compiled {
+    let i = u16::try_from(i).unwrap();
+    Box::new(move |cpu| {
+        cpu.set_i(i);
+        cpu.retire(len);
+        Ok(())
+    })
+}
+
+// Mirrors `Cpu::step`, down to setting VF before the result is computed,
+// which matters when VF is an operand.
+fn translate(op: Op, quirks: Quirks) -> Compiled {
+    let Quirks { vf_reset, shift, .. } = quirks;
+    match op {
+        Op::LD_Vx_byte { x, nn }  => registers(move |v| v[x] = nn),
+        Op::ADD_Vx_byte { x, nn } => registers(move |v| v[x] = v[x].wrapping_add(nn)),
+        Op::LD_Vx_Vy { x, y }     => registers(move |v| v[x] = v[y]),
+        Op::ADD_Vx_Vy { x, y }    => registers(move |v| {
+            v[0xf] = u8::from(v[x].checked_add(v[y]).is_none());
+            v[x] = v[x].wrapping_add(v[y]);
+        }),
+        Op::SUB_Vx_Vy { x, y }    => registers(move |v| {
+            v[0xf] = u8::from(v[x] >= v[y]);
+            v[x] = v[x].wrapping_sub(v[y]);
+        }),
+        Op::SUBN_Vx_Vy { x, y }   => registers(move |v| {
+            v[0xf] = u8::from(v[y] >= v[x]);
+            v[x] = v[y].wrapping_sub(v[x]);
+        }),
+        Op::AND_Vx_Vy { x, y } if vf_reset => registers(move |v| { v[x] &= v[y]; v[0xf] = 0; }),
+        Op::OR_Vx_Vy { x, y }  if vf_reset => registers(move |v| { v[x] |= v[y]; v[0xf] = 0; }),
+        Op::XOR_Vx_Vy { x, y } if vf_reset => registers(move |v| { v[x] ^= v[y]; v[0xf] = 0; }),
+        Op::AND_Vx_Vy { x, y }    => registers(move |v| v[x] &= v[y]),
+        Op::OR_Vx_Vy { x, y }     => registers(move |v| v[x] |= v[y]),
+        Op::XOR_Vx_Vy { x, y }    => registers(move |v| v[x] ^= v[y]),
+        Op::SHR_Vx_Vy { x, y }    => {
+            let src = if shift { x } else { y };
+            registers(move |v| {
+                let value = v[src];
+                v[0xf] = value & 0x01;
+                v[x] = value >> 1;
+            })
+        },
+        Op::SHL_Vx_Vy { x, y }    => {
+            let src = if shift { x } else { y };
+            registers(move |v| {
+                let value = v[src];
+                v[0xf] = (value & 0x80) >> 7;
+                v[x] = value << 1;
+            })
+        },
+        Op::LD_I_addr { nnn }     => load_i(nnn, 2),
+        Op::LD_I_long { nnnn }    => load_i(nnnn, 4),
+        Op::ADD_I_Vx { x }        => Box::new(move |cpu| {
+            cpu.v_mut()[0xf] = u8::from(cpu.i().checked_add(u16::from(cpu.v()[x])).is_none());
+            cpu.set_i(cpu.i().wrapping_add(u16::from(cpu.v()[x])));
+            cpu.retire(2);
+            Ok(())
+        }),
+        op                        => Box::new(move |cpu| cpu.step(&op)),
+    }
+}
@@ -0,0 +1,38 @@
+use std::fmt;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Chip8Error {
+    UnknownOpcode { pc: usize, word: u16 },
+    StackUnderflow { pc: usize },
+    StackOverflow { pc: usize },
+    MemoryOutOfBounds { pc: usize, addr: usize },
+    RomTooLarge { size: usize, max: usize },
+}
+
+impl Chip8Error {
+    // Address of the faulting instruction, if the error came from one.
+    #[must_use]
+    pub fn pc(&self) -> Option<usize> {
+        match self {
+            Chip8Error::UnknownOpcode { pc, .. }
+            | Chip8Error::StackUnderflow { pc }
+            | Chip8Error::StackOverflow { pc }
+            | Chip8Error::MemoryOutOfBounds { pc, .. } => Some(*pc),
+            Chip8Error::RomTooLarge { .. } => None,
+        }
+    }
+}
+
+impl fmt::Display for Chip8Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Chip8Error::UnknownOpcode { pc, word }     => write!(f, "unknown opcode {word:#06x} at {pc:#06x}"),
+            Chip8Error::StackUnderflow { pc }          => write!(f, "RET with an empty stack at {pc:#06x}"),
+            Chip8Error::StackOverflow { pc }           => write!(f, "CALL with a full stack at {pc:#06x}"),
+            Chip8Error::MemoryOutOfBounds { pc, addr } => write!(f, "memory access at {addr:#06x} out of bounds at {pc:#06x}"),
+            Chip8Error::RomTooLarge { size, max }      => write!(f, "ROM is {size} bytes, at most {max} fit in memory"),
+        }
+    }
+}
+
+impl std::error::Error for Chip8Error {}
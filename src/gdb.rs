@@ -0,0 +1,507 @@
+use std::fmt::Write as _;
+use std::io::{self, ErrorKind, Read, Write};
+use std::net::{SocketAddr, TcpListener, TcpStream, ToSocketAddrs};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use crate::{Chip8Error, Cpu, Debugger, Scheduler, Watch, WatchKind, TIMER_HZ};
+
+use log::{info, warn};
+
+// Register numbers for `p`/`P`. `g` sends them in this order: V0-VF, then I
+// and PC as two big-endian bytes, then SP, DT and ST as one byte each.
+pub const REG_I: usize = 16;
+pub const REG_PC: usize = 17;
+pub const REG_SP: usize = 18;
+pub const REG_DT: usize = 19;
+pub const REG_ST: usize = 20;
+pub const NUM_REGS: usize = 21;
+
+const SIGINT: u8 = 2;
+const SIGILL: u8 = 4;
+const SIGTRAP: u8 = 5;
+const SIGSEGV: u8 = 11;
+
+// Sent outside of a packet to interrupt a running target.
+const INTERRUPT: u8 = 0x03;
+
+const POLL_INTERVAL: Duration = Duration::from_millis(1);
+
+enum Packet {
+    Data(String),
+    Corrupt,
+    Interrupt,
+}
+
+enum Response {
+    Reply(String),
+    // `c`: the stop reply is sent once the program pauses.
+    Running,
+    Detach,
+    Kill,
+}
+
+struct Client {
+    stream: TcpStream,
+    buf: Vec<u8>,
+    no_ack: bool,
+}
+
+impl Client {
+    // Reads whatever has arrived without blocking. False once the client
+    // hung up.
+    fn receive(&mut self) -> io::Result<bool> {
+        self.stream.set_nonblocking(true)?;
+        let mut chunk = [0; 1024];
+        let result = loop {
+            match self.stream.read(&mut chunk) {
+                Ok(0) => break Ok(false),
+                Ok(n) => self.buf.extend_from_slice(&chunk[..n]),
+                Err(e) if e.kind() == ErrorKind::WouldBlock => break Ok(true),
+                Err(e) if e.kind() == ErrorKind::Interrupted => {}
+                Err(e) => break Err(e),
+            }
+        };
+        self.stream.set_nonblocking(false)?;
+        result
+    }
+
+    // Acks from the client carry no information for us and are skipped.
+    fn next_packet(&mut self) -> Option<Packet> {
+        let Some(start) = self.buf.iter().position(|&b| b == b'$' || b == INTERRUPT) else {
+            self.buf.clear();
+            return None;
+        };
+        self.buf.drain(..start);
+
+        if self.buf[0] == INTERRUPT {
+            self.buf.remove(0);
+            return Some(Packet::Interrupt);
+        }
+
+        let end = self.buf.iter().position(|&b| b == b'#')?;
+        if self.buf.len() < end + 3 {
+            return None;
+        }
+
+        let packet = self.buf.drain(..end + 3).collect::<Vec<_>>();
+        let data = &packet[1..end];
+        let sum = std::str::from_utf8(&packet[end + 1..]).ok().and_then(|s| u8::from_str_radix(s, 16).ok());
+        if sum == Some(checksum(data)) {
+            Some(Packet::Data(String::from_utf8_lossy(data).into_owned()))
+        } else {
+            Some(Packet::Corrupt)
+        }
+    }
+
+    fn ack(&mut self, ack: u8) -> io::Result<()> {
+        if self.no_ack {
+            return Ok(());
+        }
+        self.stream.write_all(&[ack])
+    }
+
+    fn send(&mut self, data: &str) -> io::Result<()> {
+        let packet = format!("${data}#{:02x}", checksum(data.as_bytes()));
+        self.stream.write_all(packet.as_bytes())
+    }
+}
+
+// A GDB remote serial protocol server, one client at a time. Frontends call
+// `poll` once per frame next to `Debugger::frame`; it never blocks.
+pub struct GdbStub {
+    listener: TcpListener,
+    client: Option<Client>,
+    // Set by `c` until the stop reply has been sent.
+    running: bool,
+}
+
+impl GdbStub {
+    pub fn bind<A: ToSocketAddrs>(addr: A) -> io::Result<Self> {
+        let listener = TcpListener::bind(addr)?;
+        listener.set_nonblocking(true)?;
+
+        Ok(Self { listener, client: None, running: false })
+    }
+
+    pub fn local_addr(&self) -> io::Result<SocketAddr> {
+        self.listener.local_addr()
+    }
+
+    #[must_use]
+    pub fn connected(&self) -> bool {
+        self.client.is_some()
+    }
+
+    // Accepts a waiting client, pausing the program, then answers the
+    // packets received so far. Sends the stop reply once a continue ends.
+    pub fn poll(&mut self, cpu: &mut Cpu, debugger: &mut Debugger) -> io::Result<()> {
+        if self.client.is_none() {
+            match self.listener.accept() {
+                Ok((stream, addr)) => {
+                    info!("gdb client {addr} attached");
+                    stream.set_nonblocking(false)?;
+                    stream.set_nodelay(true)?;
+                    self.client = Some(Client { stream, buf: Vec::new(), no_ack: false });
+                    self.running = false;
+                    debugger.pause();
+                }
+                Err(e) if e.kind() == ErrorKind::WouldBlock => return Ok(()),
+                Err(e) => return Err(e),
+            }
+        }
+
+        match self.process(cpu, debugger) {
+            Ok(true) => {}
+            Ok(false) => {
+                info!("gdb client detached");
+                self.disconnect(cpu, debugger);
+            }
+            Err(e) => {
+                warn!("gdb client dropped: {e}");
+                self.disconnect(cpu, debugger);
+            }
+        }
+
+        Ok(())
+    }
+
+    // Reports a crash during a continue as a signal and leaves the program
+    // paused so the client can inspect it.
+    pub fn crashed(&mut self, e: &Chip8Error, debugger: &mut Debugger) {
+        debugger.pause();
+        if !self.running {
+            return;
+        }
+
+        self.running = false;
+        if let Some(client) = &mut self.client {
+            if let Err(e) = client.send(&signal(e)) {
+                warn!("gdb client dropped: {e}");
+                self.client = None;
+            }
+        }
+    }
+
+    // Waits for a client, then runs the program in real time under its
+    // control until it detaches or kills the session.
+    pub fn serve(&mut self, cpu: &mut Cpu, scheduler: &mut Scheduler, debugger: &mut Debugger) -> io::Result<()> {
+        while !self.connected() {
+            self.poll(cpu, debugger)?;
+            thread::sleep(POLL_INTERVAL);
+        }
+
+        let frame = Duration::from_secs(1) / TIMER_HZ;
+        while self.connected() {
+            let started = Instant::now();
+            self.poll(cpu, debugger)?;
+
+            if let Err(e) = debugger.frame(scheduler, cpu) {
+                self.crashed(&e, debugger);
+            }
+
+            let wait = if debugger.paused() { POLL_INTERVAL } else { frame };
+            thread::sleep(wait.saturating_sub(started.elapsed()));
+        }
+
+        Ok(())
+    }
+
+    // Letting go of the program, as a detached gdb would.
+    fn disconnect(&mut self, cpu: &Cpu, debugger: &mut Debugger) {
+        self.client = None;
+        self.running = false;
+        debugger.resume(cpu);
+    }
+
+    // False once the client detached or killed the session.
+    fn process(&mut self, cpu: &mut Cpu, debugger: &mut Debugger) -> io::Result<bool> {
+        let Some(client) = &mut self.client else {
+            return Ok(true);
+        };
+
+        if !client.receive()? {
+            return Ok(false);
+        }
+
+        while let Some(packet) = client.next_packet() {
+            match packet {
+                Packet::Interrupt if self.running => {
+                    debugger.pause();
+                    self.running = false;
+                    client.send(&format!("S{SIGINT:02x}"))?;
+                }
+                Packet::Interrupt => {}
+                Packet::Corrupt => client.ack(b'-')?,
+                Packet::Data(data) => {
+                    client.ack(b'+')?;
+                    match handle(&data, cpu, debugger) {
+                        Response::Reply(reply) => client.send(&reply)?,
+                        Response::Running => self.running = true,
+                        Response::Detach => {
+                            client.send("OK")?;
+                            return Ok(false);
+                        }
+                        Response::Kill => return Ok(false),
+                    }
+                    if data == "QStartNoAckMode" {
+                        client.no_ack = true;
+                    }
+                }
+            }
+        }
+
+        if self.running && (debugger.paused() || cpu.halted()) {
+            self.running = false;
+            client.send(&stop_reply(cpu))?;
+        }
+
+        Ok(true)
+    }
+}
+
+fn handle(packet: &str, cpu: &mut Cpu, debugger: &mut Debugger) -> Response {
+    let mut chars = packet.chars();
+    let command = chars.next();
+    let args = chars.as_str();
+
+    let reply = match (command, args) {
+        (Some('?'), _) => Some(stop_reply(cpu)),
+        (Some('g'), _) => (0..NUM_REGS).map(|n| register(cpu, n)).collect::<Option<Vec<_>>>().map(|values| hex(&values.concat())),
+        (Some('G'), data) => write_registers(cpu, data),
+        (Some('p'), n) => parse_hex(n).filter(|n| *n < NUM_REGS).and_then(|n| register(cpu, n)).map(|value| hex(&value)),
+        (Some('P'), args) => args.split_once('=').and_then(|(n, data)| {
+            let n = parse_hex(n).filter(|n| *n < NUM_REGS)?;
+            set_register(cpu, n, &unhex(data)?)
+        }),
+        (Some('m'), args) => read_memory(cpu, args),
+        (Some('M'), args) => write_memory(cpu, args),
+        (Some('c'), addr) => {
+            if let Some(addr) = parse_hex(addr) {
+                cpu.set_pc(addr);
+            }
+            if cpu.halted() {
+                return Response::Reply(stop_reply(cpu));
+            }
+            debugger.resume(cpu);
+            return Response::Running;
+        }
+        (Some('s'), addr) => {
+            if let Some(addr) = parse_hex(addr) {
+                cpu.set_pc(addr);
+            }
+            match debugger.step(cpu) {
+                Ok(false) if !cpu.halted() => {
+                    // Blocked on `LD Vx, K`: the stop reply waits until a
+                    // key lets the instruction run, as for a continue.
+                    debugger.step_after_wait(cpu);
+                    return Response::Running;
+                }
+                Ok(_) => Some(stop_reply(cpu)),
+                Err(e) => Some(signal(&e)),
+            }
+        }
+        (Some(c @ ('Z' | 'z')), args) => return Response::Reply(point(c == 'Z', args, debugger)),
+        (Some('D'), _) => return Response::Detach,
+        (Some('k'), _) => return Response::Kill,
+        (Some('H'), _) => Some("OK".into()),
+        (Some('q'), args) if args.starts_with("Xfer:features:read:") => read_features(&args["Xfer:features:read:".len()..]),
+        _ => return Response::Reply(query(packet).into()),
+    };
+
+    Response::Reply(reply.unwrap_or_else(|| "E01".into()))
+}
+
+// Anything not listed gets the empty reply, which tells gdb it is unsupported.
+fn query(packet: &str) -> &'static str {
+    match packet.split(':').next() {
+        Some("qSupported")      => "PacketSize=1000;QStartNoAckMode+;qXfer:features:read+",
+        Some("QStartNoAckMode") => "OK",
+        Some("qAttached")       => "1",
+        Some("qC")              => "QC1",
+        Some("qfThreadInfo")    => "m1",
+        Some("qsThreadInfo")    => "l",
+        _                       => "",
+    }
+}
+
+// The register layout above as a GDB target description. There is no
+// CHIP-8 architecture to name, so it only lists the registers.
+fn target_xml() -> String {
+    let mut xml = String::from(concat!(
+        "<?xml version=\"1.0\"?>\n",
+        "<!DOCTYPE target SYSTEM \"gdb-target.dtd\">\n",
+        "<target version=\"1.0\">\n",
+        "  <feature name=\"org.rschip8.chip8\">\n",
+    ));
+    for x in 0..16 {
+        let _ = writeln!(xml, "    <reg name=\"v{x:x}\" bitsize=\"8\" type=\"uint8\" regnum=\"{x}\"/>");
+    }
+    xml.push_str(concat!(
+        "    <reg name=\"i\" bitsize=\"16\" type=\"data_ptr\"/>\n",
+        "    <reg name=\"pc\" bitsize=\"16\" type=\"code_ptr\"/>\n",
+        "    <reg name=\"sp\" bitsize=\"8\" type=\"uint8\"/>\n",
+        "    <reg name=\"dt\" bitsize=\"8\" type=\"uint8\"/>\n",
+        "    <reg name=\"st\" bitsize=\"8\" type=\"uint8\"/>\n",
+        "  </feature>\n",
+        "</target>\n",
+    ));
+    xml
+}
+
+// `<annex>:<offset>,<length>` of `qXfer:features:read`, answered in `m`
+// (more follows) or `l` (last) chunks. Only `target.xml` exists.
+fn read_features(args: &str) -> Option<String> {
+    let (annex, range) = args.split_once(':')?;
+    let (offset, len) = range.split_once(',')?;
+    let (offset, len) = (parse_hex(offset)?, parse_hex(len)?);
+    if annex != "target.xml" {
+        return Some("E00".into());
+    }
+
+    let xml = target_xml();
+    let rest = &xml[offset.min(xml.len())..];
+    Some(if rest.len() > len { format!("m{}", &rest[..len]) } else { format!("l{rest}") })
+}
+
+fn stop_reply(cpu: &Cpu) -> String {
+    if cpu.halted() {
+        "W00".into()
+    } else {
+        format!("S{SIGTRAP:02x}")
+    }
+}
+
+fn signal(e: &Chip8Error) -> String {
+    let signal = match e {
+        Chip8Error::UnknownOpcode { .. } => SIGILL,
+        _ => SIGSEGV,
+    };
+    format!("S{signal:02x}")
+}
+
+// `None` when the value does not fit the register's width on the wire.
+fn register(cpu: &Cpu, n: usize) -> Option<Vec<u8>> {
+    Some(match n {
+        REG_I  => cpu.i().to_be_bytes().to_vec(),
+        REG_PC => u16::try_from(cpu.pc()).ok()?.to_be_bytes().to_vec(),
+        REG_SP => vec![u8::try_from(cpu.sp()).ok()?],
+        REG_DT => vec![cpu.timers().delay],
+        REG_ST => vec![cpu.timers().sound],
+        _      => vec![cpu.v()[n]],
+    })
+}
+
+fn register_size(n: usize) -> usize {
+    match n {
+        REG_I | REG_PC => 2,
+        _              => 1,
+    }
+}
+
+fn set_register(cpu: &mut Cpu, n: usize, value: &[u8]) -> Option<String> {
+    match (n, value) {
+        (REG_I, &[hi, lo])  => cpu.set_i(u16::from_be_bytes([hi, lo])),
+        (REG_PC, &[hi, lo]) => cpu.set_pc(usize::from(u16::from_be_bytes([hi, lo]))),
+        (REG_SP, &[sp])     => cpu.set_sp(usize::from(sp)),
+        (REG_DT, &[dt])     => cpu.timers_mut().delay = dt,
+        (REG_ST, &[st])     => cpu.timers_mut().sound = st,
+        (0..=15, &[v])      => cpu.v_mut()[n] = v,
+        _                   => return None,
+    }
+
+    Some("OK".into())
+}
+
+fn write_registers(cpu: &mut Cpu, data: &str) -> Option<String> {
+    let mut bytes = unhex(data)?.into_iter();
+    for n in 0..NUM_REGS {
+        let value = bytes.by_ref().take(register_size(n)).collect::<Vec<_>>();
+        set_register(cpu, n, &value)?;
+    }
+
+    Some("OK".into())
+}
+
+fn memory_range(cpu: &Cpu, addr: &str, len: &str) -> Option<std::ops::Range<usize>> {
+    let addr = parse_hex(addr)?;
+    let end = addr.checked_add(parse_hex(len)?)?;
+    (end <= cpu.bus().len()).then_some(addr..end)
+}
+
+// `m<addr>,<len>`
+fn read_memory(cpu: &Cpu, args: &str) -> Option<String> {
+    let (addr, len) = args.split_once(',')?;
+    let range = memory_range(cpu, addr, len)?;
+    Some(hex(cpu.bus().peek_slice(range)))
+}
+
+// `M<addr>,<len>:<bytes>`
+fn write_memory(cpu: &mut Cpu, args: &str) -> Option<String> {
+    let (addr, rest) = args.split_once(',')?;
+    let (len, data) = rest.split_once(':')?;
+    let range = memory_range(cpu, addr, len)?;
+    let data = unhex(data).filter(|data| data.len() == range.len())?;
+    cpu.bus_mut().load(range.start, &data);
+    Some("OK".into())
+}
+
+// `Z<type>,<addr>,<kind>` inserts and `z` removes a breakpoint (types 0 and
+// 1) or a write, read or access watchpoint (types 2-4) covering `kind` bytes.
+fn point(insert: bool, args: &str, debugger: &mut Debugger) -> String {
+    let mut fields = args.split(',');
+    let (Some(kind), Some(addr), Some(len)) = (fields.next(), fields.next().and_then(parse_hex), fields.next().and_then(parse_hex)) else {
+        return "E01".into();
+    };
+
+    let Some(last) = addr.checked_add(len.max(1) - 1) else {
+        return "E01".into();
+    };
+
+    let watch = |kind| Watch::Memory { range: addr..=last, kind };
+    let watch = match kind {
+        "0" | "1" if insert => {
+            debugger.add_breakpoint(addr);
+            return "OK".into();
+        }
+        "0" | "1" => {
+            debugger.remove_breakpoint(addr);
+            return "OK".into();
+        }
+        "2" => watch(WatchKind::Write),
+        "3" => watch(WatchKind::Read),
+        "4" => watch(WatchKind::Access),
+        _ => return String::new(),
+    };
+
+    if insert {
+        debugger.add_watch(watch);
+    } else if let Some(index) = debugger.watches().iter().position(|w| *w == watch) {
+        debugger.remove_watch(index);
+    }
+
+    "OK".into()
+}
+
+fn checksum(data: &[u8]) -> u8 {
+    data.iter().fold(0, |sum, b| sum.wrapping_add(*b))
+}
+
+fn parse_hex(s: &str) -> Option<usize> {
+    usize::from_str_radix(s, 16).ok()
+}
+
+fn hex(bytes: &[u8]) -> String {
+    bytes.iter().fold(String::new(), |mut s, b| {
+        let _ = write!(s, "{b:02x}");
+        s
+    })
+}
+
+fn unhex(s: &str) -> Option<Vec<u8>> {
+    if !s.is_ascii() || !s.len().is_multiple_of(2) {
+        return None;
+    }
+
+    (0..s.len()).step_by(2).map(|n| u8::from_str_radix(&s[n..n + 2], 16).ok()).collect()
+}
@@ -0,0 +1,228 @@
+use std::fs;
+use std::io::{self, Write};
+use std::time::{Duration, Instant};
+
+use crate::{Chip8Error, Cpu, Movie, Op, Scheduler, Video};
+#[cfg(feature = "dynarec")]
+use crate::Dynarec;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum StopReason {
+    // Ran the requested number of frames.
+    Frames,
+    // The program is spinning on a `JP` to itself.
+    Loop,
+    // The program executed `EXIT`.
+    Exit,
+    Timeout,
+}
+
+impl std::fmt::Display for StopReason {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            StopReason::Frames  => write!(f, "frames"),
+            StopReason::Loop    => write!(f, "loop"),
+            StopReason::Exit    => write!(f, "exit"),
+            StopReason::Timeout => write!(f, "timeout"),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum KeyEvent {
+    Press(u8),
+    Release(u8),
+}
+
+// Key events applied at the start of the given frame.
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct InputScript {
+    events: Vec<(u64, KeyEvent)>,
+}
+
+impl InputScript {
+    #[must_use]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn push(&mut self, frame: u64, event: KeyEvent) {
+        let at = self.events.partition_point(|(f, _)| *f <= frame);
+        self.events.insert(at, (frame, event));
+    }
+
+    pub fn events_at(&self, frame: u64) -> impl Iterator<Item = KeyEvent> + '_ {
+        let start = self.events.partition_point(|(f, _)| *f < frame);
+        self.events[start..].iter().take_while(move |(f, _)| *f == frame).map(|(_, event)| *event)
+    }
+
+    // One `<frame> press|release <hex key>` event per line. `#` starts a comment.
+    pub fn parse(text: &str) -> Result<Self, String> {
+        let mut script = Self::new();
+
+        for (n, line) in text.lines().enumerate() {
+            let line = line.split('#').next().unwrap_or_default().trim();
+            if line.is_empty() {
+                continue;
+            }
+
+            let fields = line.split_whitespace().collect::<Vec<_>>();
+            let [frame, action, key] = fields[..] else {
+                return Err(format!("line {}: expected `<frame> press|release <hex key>`", n + 1));
+            };
+            let frame = frame.parse().map_err(|e| format!("line {}: frame: {e}", n + 1))?;
+            let key = u8::from_str_radix(key, 16)
+                .ok()
+                .filter(|key| *key < 16)
+                .ok_or_else(|| format!("line {}: keypad key must be 0-F", n + 1))?;
+            let event = match action {
+                "press" => KeyEvent::Press(key),
+                "release" => KeyEvent::Release(key),
+                _ => return Err(format!("line {}: expected press or release, got {action}", n + 1)),
+            };
+
+            script.push(frame, event);
+        }
+
+        Ok(script)
+    }
+
+    pub fn load(path: &str) -> io::Result<Self> {
+        Self::parse(&fs::read_to_string(path)?)
+            .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, format!("{path}: {e}")))
+    }
+}
+
+#[derive(Debug, Default, Clone, Copy)]
+pub struct Limits {
+    pub frames: Option<u64>,
+    pub stop_on_loop: bool,
+    pub timeout: Option<Duration>,
+}
+
+// Runs a `Cpu` frame by frame without a window, feeding it scripted input
+// or a movie, and optionally recording the keypad as a movie.
+#[derive(Debug)]
+pub struct Headless {
+    pub cpu: Cpu,
+    pub scheduler: Scheduler,
+    pub input: InputScript,
+    pub movie: Option<Movie>,
+    pub recording: Option<Movie>,
+    // Runs frames on compiled code instead of `Scheduler::frame` when set.
+    #[cfg(feature = "dynarec")]
+    pub dynarec: Option<Dynarec>,
+    frame: u64,
+}
+
+impl Headless {
+    #[must_use]
+    pub fn new(cpu: Cpu, scheduler: Scheduler) -> Self {
+        Self {
+            cpu,
+            scheduler,
+            input: InputScript::new(),
+            movie: None,
+            recording: None,
+            #[cfg(feature = "dynarec")]
+            dynarec: None,
+            frame: 0,
+        }
+    }
+
+    #[must_use]
+    pub fn frames(&self) -> u64 {
+        self.frame
+    }
+
+    pub fn frame(&mut self) -> Result<(), Chip8Error> {
+        for event in self.input.events_at(self.frame) {
+            match event {
+                KeyEvent::Press(key) => self.cpu.press(key),
+                KeyEvent::Release(key) => self.cpu.release(key),
+            }
+        }
+        if let Some(movie) = &self.movie {
+            movie.play(self.frame, &mut self.cpu);
+        }
+        if let Some(recording) = &mut self.recording {
+            recording.record(self.cpu.keypad());
+        }
+
+        self.run_frame()?;
+        self.frame += 1;
+        Ok(())
+    }
+
+    #[cfg(feature = "dynarec")]
+    fn run_frame(&mut self) -> Result<(), Chip8Error> {
+        match &mut self.dynarec {
+            Some(dynarec) => dynarec.frame(&mut self.scheduler, &mut self.cpu),
+            None => self.scheduler.frame(&mut self.cpu),
+        }
+    }
+
+    #[cfg(not(feature = "dynarec"))]
+    fn run_frame(&mut self) -> Result<(), Chip8Error> {
+        self.scheduler.frame(&mut self.cpu)
+    }
+
+    pub fn run(&mut self, limits: &Limits) -> Result<StopReason, Chip8Error> {
+        let started = Instant::now();
+
+        loop {
+            if self.cpu.halted() {
+                return Ok(StopReason::Exit);
+            }
+            if limits.frames.is_some_and(|frames| self.frame >= frames) {
+                return Ok(StopReason::Frames);
+            }
+            if limits.stop_on_loop && self.looping() {
+                return Ok(StopReason::Loop);
+            }
+            if limits.timeout.is_some_and(|timeout| started.elapsed() >= timeout) {
+                return Ok(StopReason::Timeout);
+            }
+
+            self.frame()?;
+        }
+    }
+
+    fn looping(&self) -> bool {
+        matches!(self.cpu.current(), (pc, _, Op::JP_addr { nnn }) if nnn == pc)
+    }
+}
+
+// One character per pixel: `.` when off, `#` on plane 1, otherwise the
+// plane mask as a digit.
+#[must_use]
+pub fn dump_text(video: &Video) -> String {
+    let mut text = String::new();
+    for y in 0..video.height() {
+        for x in 0..video.width() {
+            text.push(match video.ram[video.map_idx(x, y)] {
+                0 => '.',
+                1 => '#',
+                p => char::from(b'0' + p),
+            });
+        }
+        text.push('\n');
+    }
+
+    text
+}
+
+// Grayscale PNG, one image pixel per screen pixel.
+#[allow(clippy::cast_sign_loss)]
+pub fn write_png<W: Write>(video: &Video, out: W) -> io::Result<()> {
+    const SHADES: [u8; 4] = [0x00, 0xff, 0x80, 0xc0];
+
+    let mut encoder = png::Encoder::new(out, video.width() as u32, video.height() as u32);
+    encoder.set_color(png::ColorType::Grayscale);
+    encoder.set_depth(png::BitDepth::Eight);
+
+    let data = video.ram.iter().map(|p| SHADES[usize::from(*p & 3)]).collect::<Vec<_>>();
+    encoder.write_header()
+        .and_then(|mut writer| writer.write_image_data(&data))
+        .map_err(|e| io::Error::other(e.to_string()))
+}
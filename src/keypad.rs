@@ -0,0 +1,185 @@
+use std::collections::HashMap;
+use std::fs;
+use std::io;
+
+pub const NUM_KEYS: usize = 16;
+
+// Host keys for the COSMAC VIP hex keypad, laid out on a QWERTY keyboard:
+//   1 2 3 C        1 2 3 4
+//   4 5 6 D   <-   Q W E R
+//   7 8 9 E        A S D F
+//   A 0 B F        Z X C V
+const QWERTY: [(char, u8); NUM_KEYS] = [
+    ('1', 0x1), ('2', 0x2), ('3', 0x3), ('4', 0xc),
+    ('q', 0x4), ('w', 0x5), ('e', 0x6), ('r', 0xd),
+    ('a', 0x7), ('s', 0x8), ('d', 0x9), ('f', 0xe),
+    ('z', 0xa), ('x', 0x0), ('c', 0xb), ('v', 0xf),
+];
+
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct Keypad {
+    pressed: [bool; NUM_KEYS],
+}
+
+impl Keypad {
+    #[must_use]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn press(&mut self, key: u8) {
+        if let Some(pressed) = self.pressed.get_mut(usize::from(key)) {
+            *pressed = true;
+        }
+    }
+
+    pub fn release(&mut self, key: u8) {
+        if let Some(pressed) = self.pressed.get_mut(usize::from(key)) {
+            *pressed = false;
+        }
+    }
+
+    pub fn release_all(&mut self) {
+        self.pressed = [false; NUM_KEYS];
+    }
+
+    #[must_use]
+    pub fn is_pressed(&self, key: u8) -> bool {
+        self.pressed.get(usize::from(key)).copied().unwrap_or(false)
+    }
+
+    #[must_use]
+    pub fn first_pressed(&self) -> Option<u8> {
+        (0..).zip(self.pressed).find(|(_, pressed)| *pressed).map(|(key, _)| key)
+    }
+
+    // Bit `n` set means key `n` is down.
+    #[must_use]
+    pub fn bits(&self) -> u16 {
+        (0..).zip(self.pressed).fold(0, |bits, (key, pressed)| bits | u16::from(pressed) << key)
+    }
+}
+
+// Maps host keys (lowercase characters) to hex keypad keys.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct KeyMap {
+    map: HashMap<char, u8>,
+}
+
+impl Default for KeyMap {
+    fn default() -> Self {
+        Self { map: QWERTY.iter().copied().collect() }
+    }
+}
+
+impl KeyMap {
+    #[must_use]
+    pub fn get(&self, host: char) -> Option<u8> {
+        self.map.get(&host.to_ascii_lowercase()).copied()
+    }
+
+    // Binds `host` to `key`, replacing whatever host key `key` had before.
+    pub fn set(&mut self, host: char, key: u8) -> Result<(), String> {
+        if usize::from(key) >= NUM_KEYS {
+            return Err(format!("keypad key {key:#x} is out of range, expected 0-F"));
+        }
+        self.map.retain(|_, k| *k != key);
+        self.map.insert(host.to_ascii_lowercase(), key);
+        Ok(())
+    }
+
+    // Starts from the QWERTY layout and applies one `<host key> = <hex key>`
+    // binding per line. `#` starts a comment.
+    pub fn parse(text: &str) -> Result<Self, String> {
+        let mut keymap = Self::default();
+
+        for (n, line) in text.lines().enumerate() {
+            let line = line.split('#').next().unwrap_or_default().trim();
+            if line.is_empty() {
+                continue;
+            }
+
+            let (host, key) = line.split_once('=')
+                .ok_or_else(|| format!("line {}: expected `<host key> = <hex key>`", n + 1))?;
+            let mut host = host.trim().chars();
+            let (Some(host), None) = (host.next(), host.next()) else {
+                return Err(format!("line {}: host key must be a single character", n + 1));
+            };
+            let key = u8::from_str_radix(key.trim(), 16)
+                .ok()
+                .filter(|key| usize::from(*key) < NUM_KEYS)
+                .ok_or_else(|| format!("line {}: keypad key must be 0-F", n + 1))?;
+
+            keymap.set(host, key).map_err(|e| format!("line {}: {e}", n + 1))?;
+        }
+
+        Ok(keymap)
+    }
+
+    pub fn load(path: &str) -> io::Result<Self> {
+        Self::parse(&fs::read_to_string(path)?)
+            .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, format!("{path}: {e}")))
+    }
+}
+
+// How `LD Vx, K` resolves: the COSMAC VIP waits for a key to be pressed and
+// then released, later interpreters return as soon as a key goes down.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum KeyWaitMode {
+    #[default]
+    Vip,
+    Modern,
+}
+
+impl std::str::FromStr for KeyWaitMode {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "vip" => Ok(KeyWaitMode::Vip),
+            "modern" => Ok(KeyWaitMode::Modern),
+            _ => Err(format!("unknown key wait mode {s}, expected vip|modern")),
+        }
+    }
+}
+
+// State of a blocking `LD Vx, K`, advanced by key press and release edges.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum KeyWait {
+    #[default]
+    Idle,
+    Press { x: usize },
+    Release { x: usize, key: u8 },
+}
+
+impl KeyWait {
+    #[must_use]
+    pub fn waiting(&self) -> bool {
+        *self != KeyWait::Idle
+    }
+
+    // Returns the register and key to store once the wait is over.
+    pub fn press(&mut self, key: u8, mode: KeyWaitMode) -> Option<(usize, u8)> {
+        match (*self, mode) {
+            (KeyWait::Press { x }, KeyWaitMode::Modern) => {
+                *self = KeyWait::Idle;
+                Some((x, key))
+            }
+            (KeyWait::Press { x }, KeyWaitMode::Vip) => {
+                *self = KeyWait::Release { x, key };
+                None
+            }
+            _ => None,
+        }
+    }
+
+    pub fn release(&mut self, key: u8) -> Option<(usize, u8)> {
+        match *self {
+            KeyWait::Release { x, key: pressed } if pressed == key => {
+                *self = KeyWait::Idle;
+                Some((x, key))
+            }
+            _ => None,
+        }
+    }
+}
@@ -0,0 +1,56 @@
+#![warn(clippy::pedantic)]
+#![allow(clippy::missing_errors_doc, clippy::missing_panics_doc)]
+
+pub mod error;
+pub mod video;
+pub mod bus;
+pub mod cpu;
+pub mod op;
+pub mod timer;
+pub mod random;
+pub mod scheduler;
+pub mod audio;
+pub mod keypad;
+pub mod quirks;
+pub mod headless;
+pub mod debugger;
+pub mod disasm;
+pub mod gdb;
+pub mod savestate;
+pub mod rewind;
+pub mod movie;
+pub mod asm;
+pub mod octo;
+#[cfg(feature = "dynarec")]
+pub mod dynarec;
+
+pub mod prelude {
+    pub const SCREEN_WIDTH: i32 = 64;
+    pub const SCREEN_HEIGHT: i32 = 32;
+    pub const HIRES_WIDTH: i32 = 128;
+    pub const HIRES_HEIGHT: i32 = 64;
+    pub use crate::error::*;
+    pub use crate::video::*;
+    pub use crate::bus::*;
+    pub use crate::cpu::*;
+    pub use crate::op::*;
+    pub use crate::timer::*;
+    pub use crate::random::*;
+    pub use crate::scheduler::*;
+    pub use crate::audio::*;
+    pub use crate::keypad::*;
+    pub use crate::quirks::*;
+    pub use crate::headless::*;
+    pub use crate::debugger::*;
+    pub use crate::disasm::*;
+    pub use crate::gdb::*;
+    pub use crate::savestate::*;
+    pub use crate::rewind::*;
+    pub use crate::movie::*;
+    pub use crate::asm::*;
+    pub use crate::octo::*;
+    #[cfg(feature = "dynarec")]
+    pub use crate::dynarec::*;
+}
+
+pub use prelude::*;
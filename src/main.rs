@@ -1,81 +1,359 @@
 #![warn(clippy::pedantic)]
 
-mod video;
-mod cpu;
-mod op;
-
-mod prelude {
-    pub use bracket_lib::prelude::*;
-    pub const SCREEN_WIDTH: i32 = 64;
-    pub const SCREEN_HEIGHT: i32 = 32;
-    pub use crate::video::*;
-    pub use crate::cpu::*;
-    pub use crate::op::*;
-}
-
-use prelude::*;
+#[cfg(feature = "terminal")]
+mod terminal;
 
-extern crate rand;
+use rschip8::prelude::*;
 
 use std::env;
-use std::fs;
+use std::fs::{self, File};
+use std::error::Error;
+use std::io::BufWriter;
+use std::time::Duration;
+
+const USAGE: &str = "Usage: rschip8 r|d|headless <rom> [options]
+       rschip8 asm <source|source.8o> [--out <rom>]
+
+  --hz <n>                    instructions per second
+  --quirks <preset>           vip|chip48|schip|xochip
+  --key-wait <mode>           vip|modern
+  --seed <n>                  seed for RND, random by default
+  --audio <sink>              device|null|<file.wav>, device needs aplay (r)
+  --keymap <file>             host key bindings (r)
+  --break <addr>              pause at a PC breakpoint, repeatable (r, --gdb)
+  --watch <spec>              pause on a memory or register watch, repeatable (r, --gdb)
+  --rewind <KiB>              memory for rewinding with Backspace (r)
+  --gdb <port>                serve the GDB remote protocol on localhost (r, headless)
+  --frames <n>                stop after n frames (headless)
+  --stop-on-loop              stop on a jump to itself (headless)
+  --timeout <seconds>         stop after a wall clock timeout (headless)
+  --input <file>              scripted key presses (headless)
+  --dump <file.txt|file.png>  write the final screen (headless)
+  --record <file>             record the keypad to a movie (r, headless)
+  --play <file>               replay a movie, checking the final screen (r, headless)
+  --dynarec                   run on the block recompiler, if built with it (headless)
+  --out <file>                where to write the ROM, <source>.ch8 by default (asm)";
 
-extern crate log;
-use log::{debug};
+const DEFAULT_HEADLESS_FRAMES: u64 = 600;
 
-impl GameState for Cpu {
-    fn tick(&mut self, ctx: &mut BTerm) {
-        ctx.cls();
-        self.video.render(ctx);
+struct Options {
+    command: String,
+    filename: String,
+    clock_hz: u32,
+    audio: String,
+    keymap: Option<String>,
+    key_wait: KeyWaitMode,
+    quirks: Option<Quirks>,
+    limits: Limits,
+    input: Option<String>,
+    dump: Option<String>,
+    breakpoints: Vec<usize>,
+    watches: Vec<Watch>,
+    gdb: Option<u16>,
+    rewind: usize,
+    seed: Option<u64>,
+    record: Option<String>,
+    play: Option<String>,
+    out: Option<String>,
+    #[cfg(feature = "dynarec")]
+    dynarec: bool,
+}
+
+fn parse_args() -> Result<Options, Box<dyn Error + Send + Sync>> {
+    let mut args = env::args().skip(1);
 
-        if ! self.waiting_key() {
-            let (pc, word, op) = self.current();
-            debug!("{:#06x} {:#06x} {}", pc, word, op);
-            self.step(&op);
+    let command = args.next().ok_or(USAGE)?;
+    let filename = args.next().ok_or(USAGE)?;
+    let mut options = Options {
+        command,
+        filename,
+        clock_hz: DEFAULT_CLOCK_HZ,
+        audio: if cfg!(target_os = "linux") { "device" } else { "null" }.into(),
+        keymap: None,
+        key_wait: KeyWaitMode::default(),
+        quirks: None,
+        limits: Limits::default(),
+        input: None,
+        dump: None,
+        breakpoints: Vec::new(),
+        watches: Vec::new(),
+        gdb: None,
+        rewind: DEFAULT_REWIND_BUDGET,
+        seed: None,
+        record: None,
+        play: None,
+        out: None,
+        #[cfg(feature = "dynarec")]
+        dynarec: false,
+    };
+
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--hz" => {
+                options.clock_hz = args.next().ok_or(USAGE)?.parse()?;
+            },
+            "--audio" => {
+                options.audio = args.next().ok_or(USAGE)?;
+            },
+            "--keymap" => {
+                options.keymap = Some(args.next().ok_or(USAGE)?);
+            },
+            "--key-wait" => {
+                options.key_wait = args.next().ok_or(USAGE)?.parse()?;
+            },
+            "--seed" => {
+                options.seed = Some(args.next().ok_or(USAGE)?.parse()?);
+            },
+            "--quirks" => {
+                options.quirks = Some(args.next().ok_or(USAGE)?.parse()?);
+            },
+            "--frames" => {
+                options.limits.frames = Some(args.next().ok_or(USAGE)?.parse()?);
+            },
+            "--stop-on-loop" => {
+                options.limits.stop_on_loop = true;
+            },
+            "--timeout" => {
+                options.limits.timeout = Some(Duration::from_secs_f64(args.next().ok_or(USAGE)?.parse()?));
+            },
+            "--input" => {
+                options.input = Some(args.next().ok_or(USAGE)?);
+            },
+            "--record" => {
+                options.record = Some(args.next().ok_or(USAGE)?);
+            },
+            "--play" => {
+                options.play = Some(args.next().ok_or(USAGE)?);
+            },
+            "--out" => {
+                options.out = Some(args.next().ok_or(USAGE)?);
+            },
+            "--dump" => {
+                options.dump = Some(args.next().ok_or(USAGE)?);
+            },
+            "--break" => {
+                let addr = args.next().ok_or(USAGE)?;
+                options.breakpoints.push(usize::from_str_radix(addr.trim_start_matches("0x"), 16)?);
+            },
+            "--watch" => {
+                options.watches.push(args.next().ok_or(USAGE)?.parse()?);
+            },
+            "--rewind" => {
+                options.rewind = args.next().ok_or(USAGE)?.parse::<usize>()? * 1024;
+            },
+            #[cfg(feature = "dynarec")]
+            "--dynarec" => {
+                options.dynarec = true;
+            },
+            "--gdb" => {
+                options.gdb = Some(args.next().ok_or(USAGE)?.parse()?);
+            },
+            _ => return Err(format!("Unknown option {arg}\n{USAGE}").into())
         }
+    }
 
-        match ctx.key {
-            None => {
-                self.release();
-            }
-            Some(key) => {
-                if key == VirtualKeyCode::Q {
-                    ctx.quitting = true;
-                } else {
-                    self.press(key as u8);
-                }
+    // Movies replay whole frames, so nothing may stop the CPU mid-frame.
+    let debugging = options.gdb.is_some() || !options.breakpoints.is_empty() || !options.watches.is_empty();
+    if debugging && (options.record.is_some() || options.play.is_some()) {
+        return Err("--break, --watch and --gdb cannot be combined with --record or --play".into());
+    }
+
+    Ok(options)
+}
+
+#[cfg(feature = "terminal")]
+fn open_audio(spec: &str) -> Result<Box<dyn AudioSink>, Box<dyn Error + Send + Sync>> {
+    match spec {
+        "null" => Ok(Box::new(NullSink)),
+        "device" => match DeviceSink::open() {
+            Ok(sink) => Ok(Box::new(sink)),
+            Err(e) => {
+                log::warn!("no audio device: {e}");
+                Ok(Box::new(NullSink))
             }
-        }
+        },
+        path => Ok(Box::new(WavSink::new(BufWriter::new(File::create(path)?))?)),
+    }
+}
+
+// `--quirks` wins over a `<rom>.quirks` file next to the ROM.
+fn rom_quirks(options: &Options) -> Result<Quirks, Box<dyn Error + Send + Sync>> {
+    if let Some(quirks) = options.quirks {
+        return Ok(quirks);
+    }
+
+    let sidecar = std::path::Path::new(&options.filename).with_extension("quirks");
+    if sidecar.exists() {
+        return Ok(Quirks::load(&sidecar.to_string_lossy())?);
+    }
+
+    Ok(Quirks::default())
+}
+
+fn new_cpu(rom: &[u8], options: &Options) -> Result<Cpu, Box<dyn Error + Send + Sync>> {
+    let mut cpu = Cpu::new(rom)?;
+    cpu.set_key_wait_mode(options.key_wait);
+    cpu.set_quirks(rom_quirks(options)?);
+    if let Some(seed) = options.seed {
+        cpu.set_seed(seed);
     }
+
+    Ok(cpu)
+}
+
+// `--play` sets the CPU and clock up the way the movie was recorded.
+fn open_movie(cpu: &mut Cpu, options: &Options) -> Result<(Option<Movie>, Scheduler), Box<dyn Error + Send + Sync>> {
+    let Some(path) = &options.play else {
+        return Ok((None, Scheduler::new(options.clock_hz)));
+    };
+
+    let movie = Movie::load(path)?;
+    movie.start(cpu)?;
+    let scheduler = Scheduler::new(movie.clock_hz);
+    Ok((Some(movie), scheduler))
 }
 
-fn main() -> BError {
-    if env::args().len() != 3 {
-        panic!()
+fn new_debugger(options: &Options) -> Debugger {
+    let mut debugger = Debugger::new();
+    for addr in &options.breakpoints {
+        debugger.add_breakpoint(*addr);
     }
+    for watch in &options.watches {
+        debugger.add_watch(watch.clone());
+    }
+
+    debugger
+}
 
-    let command = env::args().nth(1).ok_or("Command r|a")?;
-    let filename = env::args().nth(2).ok_or("Provide path to rom")?;
-    let rom = fs::read(filename.clone()).expect("Unable to read file");
-    let state = Cpu::new(rom.as_slice());
+fn open_gdb(options: &Options) -> Result<Option<GdbStub>, Box<dyn Error + Send + Sync>> {
+    let Some(port) = options.gdb else {
+        return Ok(None);
+    };
 
-    match command.as_str() {
+    let gdb = GdbStub::bind(("127.0.0.1", port))?;
+    eprintln!("gdb: listening on {}", gdb.local_addr()?);
+    Ok(Some(gdb))
+}
+
+// Runs under a gdb client's control until it detaches.
+fn headless_gdb(rom: &[u8], options: &Options, mut gdb: GdbStub) -> Result<(), Box<dyn Error + Send + Sync>> {
+    let mut cpu = new_cpu(rom, options)?;
+    let mut debugger = new_debugger(options);
+    gdb.serve(&mut cpu, &mut Scheduler::new(options.clock_hz), &mut debugger)?;
+    eprintln!("detached at pc {:#06x}", cpu.pc());
+
+    Ok(())
+}
+
+fn headless(rom: &[u8], options: &Options) -> Result<(), Box<dyn Error + Send + Sync>> {
+    if let Some(gdb) = open_gdb(options)? {
+        return headless_gdb(rom, options, gdb);
+    }
+
+    let mut cpu = new_cpu(rom, options)?;
+    let (movie, scheduler) = open_movie(&mut cpu, options)?;
+    let mut runner = Headless::new(cpu, scheduler);
+    if let Some(input) = &options.input {
+        runner.input = InputScript::load(input)?;
+    }
+    if options.record.is_some() {
+        runner.recording = Some(Movie::new(&runner.cpu, runner.scheduler.clock_hz()));
+    }
+
+    let mut limits = options.limits;
+    if limits.frames.is_none() && limits.timeout.is_none() {
+        limits.frames = Some(movie.as_ref().map_or(DEFAULT_HEADLESS_FRAMES, |movie| movie.frames.len() as u64));
+    }
+    runner.movie = movie;
+    #[cfg(feature = "dynarec")]
+    if options.dynarec {
+        runner.dynarec = Some(Dynarec::new());
+    }
+
+    let result = runner.run(&limits);
+    match &result {
+        Ok(reason) => eprintln!("stopped: {reason} after {} frames at pc {:#06x}", runner.frames(), runner.cpu.pc()),
+        Err(e) => eprintln!("crashed: {e} after {} frames\n{}", runner.frames(), runner.cpu),
+    }
+
+    match options.dump.as_deref() {
+        Some(path) if std::path::Path::new(path).extension().is_some_and(|ext| ext.eq_ignore_ascii_case("png")) => {
+            write_png(&runner.cpu.video, BufWriter::new(File::create(path)?))?;
+        },
+        Some(path) => {
+            fs::write(path, dump_text(&runner.cpu.video))?;
+        },
+        None => {
+            print!("{}", dump_text(&runner.cpu.video));
+        }
+    }
+
+    if let (Some(path), Some(recording)) = (&options.record, &mut runner.recording) {
+        recording.finish(&runner.cpu.video);
+        recording.save(path)?;
+    }
+    match runner.movie.as_ref().and_then(|movie| movie.verify(&runner.cpu.video)) {
+        Some(true) => eprintln!("movie: screen matches the recording"),
+        Some(false) => return Err("movie: screen differs from the recording".into()),
+        None => {}
+    }
+
+    result.map(|_| ()).map_err(Into::into)
+}
+
+// `.8o` sources are Octo, anything else the disassembler's syntax.
+fn asm(options: &Options) -> Result<(), Box<dyn Error + Send + Sync>> {
+    let octo = std::path::Path::new(&options.filename).extension().is_some_and(|ext| ext.eq_ignore_ascii_case("8o"));
+    let rom = if octo { compile_octo_file(&options.filename) } else { assemble_file(&options.filename) };
+    let rom = rom.map_err(|e| {
+        eprintln!("{e}");
+        "assembly failed"
+    })?;
+    let out = options.out.clone()
+        .unwrap_or_else(|| std::path::Path::new(&options.filename).with_extension("ch8").to_string_lossy().into());
+    fs::write(&out, &rom)?;
+    eprintln!("{out}: {} bytes", rom.len());
+
+    Ok(())
+}
+
+fn main() -> Result<(), Box<dyn Error + Send + Sync>> {
+    let options = parse_args()?;
+    if options.command == "asm" {
+        return asm(&options);
+    }
+    let rom = fs::read(&options.filename)?;
+
+    match options.command.as_str() {
+        #[cfg(feature = "terminal")]
         "r" => {
             env_logger::init();
 
-            let context = BTermBuilder::simple(SCREEN_WIDTH, SCREEN_HEIGHT)
-                .unwrap()
-                .with_title(filename)
-                .with_fps_cap(60.0)
-                .build()?;
+            let mut cpu = new_cpu(&rom, &options)?;
+            let (movie, scheduler) = open_movie(&mut cpu, &options)?;
+            let recording = options.record.as_ref().map(|path| (Movie::new(&cpu, scheduler.clock_hz()), path.clone()));
+            let mut terminal = terminal::Terminal::new(
+                cpu,
+                scheduler,
+                open_audio(&options.audio)?,
+                options.keymap.as_deref().map_or_else(|| Ok(KeyMap::default()), KeyMap::load)?,
+                new_debugger(&options),
+                open_gdb(&options)?,
+                &options.filename
+            );
+            terminal.rewind = Rewind::new(options.rewind);
+            terminal.playback = movie;
+            terminal.recording = recording;
 
-            main_loop(context, state)
+            terminal::run(terminal)
         },
         "d" => {
-            Cpu::disassemble(&rom);
+            print!("{}", Disassembly::new(&rom)?);
             Ok(())
         },
-        _ => panic!("Unknown command")
+        "headless" => {
+            headless(&rom, &options)
+        },
+        _ => Err(USAGE.into())
     }
-
 }
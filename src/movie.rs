@@ -0,0 +1,159 @@
+use std::fs;
+use std::io;
+
+use crate::{rom_hash, Cpu, KeyWaitMode, Keypad, Quirks, Video};
+use crate::savestate::{quirk_bits, quirks_from_bits};
+
+pub const MOVIE_MAGIC: &[u8; 4] = b"C8MV";
+pub const MOVIE_VERSION: u16 = 1;
+
+// The keypad state of every frame of a run, plus what it takes to replay it
+// exactly: the ROM, the `RND` seed, quirks, key wait mode and clock.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Movie {
+    pub rom_hash: u64,
+    pub seed: u64,
+    pub quirks: Quirks,
+    pub key_wait_mode: KeyWaitMode,
+    pub clock_hz: u32,
+    // `Keypad::bits` at the start of each frame.
+    pub frames: Vec<u16>,
+    // Hash of `Video::ram` when recording stopped, checked after playback.
+    pub video_hash: Option<u64>,
+}
+
+impl Movie {
+    // An empty recording of `cpu`, which should not have run yet.
+    #[must_use]
+    pub fn new(cpu: &Cpu, clock_hz: u32) -> Self {
+        Self {
+            rom_hash: cpu.rom_hash(),
+            seed: cpu.seed(),
+            quirks: *cpu.quirks(),
+            key_wait_mode: cpu.key_wait_mode(),
+            clock_hz,
+            frames: Vec::new(),
+            video_hash: None,
+        }
+    }
+
+    pub fn record(&mut self, keypad: &Keypad) {
+        self.frames.push(keypad.bits());
+    }
+
+    pub fn finish(&mut self, video: &Video) {
+        self.video_hash = Some(video_hash(video));
+    }
+
+    // Sets a fresh `cpu` up the way the recording started.
+    pub fn start(&self, cpu: &mut Cpu) -> Result<(), String> {
+        if cpu.rom_hash() != self.rom_hash {
+            return Err(format!("movie is for ROM {:016x}, not {:016x}", self.rom_hash, cpu.rom_hash()));
+        }
+
+        cpu.set_seed(self.seed);
+        cpu.set_quirks(self.quirks);
+        cpu.set_key_wait_mode(self.key_wait_mode);
+        Ok(())
+    }
+
+    // Presses and releases keys so the keypad matches `frame`. False once
+    // the recording has run out.
+    pub fn play(&self, frame: u64, cpu: &mut Cpu) -> bool {
+        let Some(bits) = usize::try_from(frame).ok().and_then(|frame| self.frames.get(frame)) else {
+            return false;
+        };
+
+        for key in 0..16 {
+            match (bits & (1 << key) != 0, cpu.keypad().is_pressed(key)) {
+                (true, false) => cpu.press(key),
+                (false, true) => cpu.release(key),
+                _ => {}
+            }
+        }
+
+        true
+    }
+
+    // Compares the screen after playback with the one recorded; `None` when
+    // the recording did not keep it.
+    #[must_use]
+    pub fn verify(&self, video: &Video) -> Option<bool> {
+        self.video_hash.map(|hash| hash == video_hash(video))
+    }
+
+    #[must_use]
+    pub fn encode(&self) -> Vec<u8> {
+        let mut out = Vec::with_capacity(self.frames.len() * 2 + 48);
+        out.extend_from_slice(MOVIE_MAGIC);
+        out.extend_from_slice(&MOVIE_VERSION.to_le_bytes());
+        out.extend_from_slice(&self.rom_hash.to_le_bytes());
+        out.extend_from_slice(&self.seed.to_le_bytes());
+        out.push(quirk_bits(self.quirks));
+        out.push(u8::from(self.key_wait_mode == KeyWaitMode::Modern));
+        out.extend_from_slice(&self.clock_hz.to_le_bytes());
+        match self.video_hash {
+            Some(hash) => {
+                out.push(1);
+                out.extend_from_slice(&hash.to_le_bytes());
+            }
+            None => out.push(0),
+        }
+        out.extend_from_slice(&u32::try_from(self.frames.len()).unwrap().to_le_bytes());
+        for bits in &self.frames {
+            out.extend_from_slice(&bits.to_le_bytes());
+        }
+
+        out
+    }
+
+    pub fn decode(data: &[u8]) -> Result<Self, String> {
+        let mut data = data;
+        let mut take = |len: usize| {
+            if data.len() < len {
+                return Err("movie is truncated".to_string());
+            }
+            let (bytes, rest) = data.split_at(len);
+            data = rest;
+            Ok(bytes)
+        };
+
+        if take(4)? != MOVIE_MAGIC {
+            return Err("not a movie file".into());
+        }
+        let version = u16::from_le_bytes(take(2)?.try_into().unwrap());
+        if version != MOVIE_VERSION {
+            return Err(format!("unsupported movie version {version}"));
+        }
+
+        let rom_hash = u64::from_le_bytes(take(8)?.try_into().unwrap());
+        let seed = u64::from_le_bytes(take(8)?.try_into().unwrap());
+        let quirks = quirks_from_bits(take(1)?[0]);
+        let key_wait_mode = if take(1)?[0] == 0 { KeyWaitMode::Vip } else { KeyWaitMode::Modern };
+        let clock_hz = u32::from_le_bytes(take(4)?.try_into().unwrap());
+        let video_hash = match take(1)?[0] {
+            0 => None,
+            _ => Some(u64::from_le_bytes(take(8)?.try_into().unwrap())),
+        };
+        let len = u32::from_le_bytes(take(4)?.try_into().unwrap());
+        let frames = (0..len)
+            .map(|_| take(2).map(|bits| u16::from_le_bytes(bits.try_into().unwrap())))
+            .collect::<Result<_, _>>()?;
+
+        Ok(Self { rom_hash, seed, quirks, key_wait_mode, clock_hz, frames, video_hash })
+    }
+
+    pub fn save(&self, path: &str) -> io::Result<()> {
+        fs::write(path, self.encode())
+    }
+
+    pub fn load(path: &str) -> io::Result<Self> {
+        Self::decode(&fs::read(path)?)
+            .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, format!("{path}: {e}")))
+    }
+}
+
+// The same FNV-1a hash as for ROMs, over the screen's pixels.
+fn video_hash(video: &Video) -> u64 {
+    rom_hash(&video.ram)
+}
@@ -0,0 +1,782 @@
+use std::collections::HashMap;
+use std::f64::consts::{E, PI};
+use std::path::{Path, PathBuf};
+use std::rc::Rc;
+
+use crate::asm::{read_source, Loc};
+use crate::{AsmError, PROGRAM_BASE, RAM_SIZE};
+
+// Macros expanding to more tokens than this are taken to be recursive.
+const MAX_EXPANSION: usize = 1 << 20;
+
+#[derive(Debug, Clone)]
+struct Token {
+    text: String,
+    loc: Loc,
+}
+
+// Octo separates tokens with whitespace only; `#` comments to the end of the
+// line.
+fn tokenize(file: &Rc<str>, text: &str) -> Vec<Token> {
+    let mut tokens = Vec::new();
+    for (n, line) in text.lines().enumerate() {
+        let line = line.split('#').next().unwrap_or("");
+        let mut start = None;
+        for (col, c) in line.chars().chain([' ']).enumerate() {
+            match (c.is_whitespace(), start) {
+                (false, None) => start = Some(col),
+                (true, Some(from)) => {
+                    let loc = Loc { file: file.clone(), line: n + 1, col: from + 1 };
+                    tokens.push(Token { text: line.chars().skip(from).take(col - from).collect(), loc });
+                    start = None;
+                }
+                _ => {}
+            }
+        }
+    }
+    tokens
+}
+
+fn parse_number(text: &str) -> Option<f64> {
+    let (negative, digits) = text.strip_prefix('-').map_or((false, text), |digits| (true, digits));
+    let value = if let Some(hex) = digits.strip_prefix("0x") {
+        u32::from_str_radix(hex, 16).ok().map(f64::from)
+    } else if let Some(bin) = digits.strip_prefix("0b") {
+        u32::from_str_radix(bin, 2).ok().map(f64::from)
+    } else if digits.starts_with(|c: char| c.is_ascii_digit()) {
+        digits.parse().ok()
+    } else {
+        None
+    }?;
+    Some(if negative { -value } else { value })
+}
+
+fn is_name(text: &str) -> bool {
+    text.starts_with(|c: char| c.is_alphabetic() || c == '_')
+        && text.chars().all(|c| c.is_alphanumeric() || c == '_' || c == '-')
+}
+
+#[derive(Debug)]
+struct Macro {
+    args: Vec<String>,
+    body: Vec<Token>,
+}
+
+// How a forward reference is patched in once its label is known.
+#[derive(Debug, Clone, Copy)]
+enum Fixup {
+    // Low 12 bits of the instruction at the address.
+    Addr12,
+    // The whole 16-bit word at the address.
+    Addr16,
+    // The `v0 := hi` `v1 := lo` pair `:unpack` writes at the address.
+    Unpack,
+}
+
+#[derive(Debug)]
+struct Pending {
+    name: String,
+    addr: usize,
+    kind: Fixup,
+    loc: Loc,
+}
+
+// A parsed `vx <op> rhs` for `if` and `while`, emitted once it is known
+// whether it guards one statement or a jump.
+struct Condition {
+    x: u8,
+    op: Token,
+    rhs: Option<Token>,
+}
+
+// Single pass over the token stream with forward references patched at the
+// end, as Octo itself does.
+#[derive(Default)]
+struct Octo {
+    // Still to compile, last token first so macros can push their bodies.
+    tokens: Vec<Token>,
+    expanded: usize,
+    end_of_input: Option<Loc>,
+    rom: Vec<u8>,
+    here: usize,
+    end: usize,
+    labels: HashMap<String, usize>,
+    constants: HashMap<String, f64>,
+    aliases: HashMap<String, u8>,
+    macros: HashMap<String, Macro>,
+    fixups: Vec<Pending>,
+    // `loop` addresses with the `while` jumps out of them.
+    loops: Vec<(usize, Vec<usize>, Loc)>,
+    // Jumps of `if ... begin` and `else` waiting for their target.
+    branches: Vec<(usize, Loc)>,
+    // Whether a jump to `main` went first, once anything has been laid out.
+    main_jump: Option<bool>,
+}
+
+impl Octo {
+    fn new(file: &Rc<str>, text: &str) -> Self {
+        let mut tokens = tokenize(file, text);
+        let end_of_input = tokens.last().map(|token| token.loc.clone());
+        tokens.reverse();
+        Self { tokens, end_of_input, rom: vec![0; RAM_SIZE], here: PROGRAM_BASE, end: PROGRAM_BASE, ..Self::default() }
+    }
+
+    fn next(&mut self, what: &str) -> Result<Token, AsmError> {
+        self.tokens.pop().ok_or_else(|| {
+            let loc = self.end_of_input.clone().unwrap_or(Loc { file: "".into(), line: 1, col: 1 });
+            loc.error(format!("expected {what} at end of input"))
+        })
+    }
+
+    fn eat(&mut self, text: &str) -> bool {
+        if self.tokens.last().is_some_and(|token| token.text == text) {
+            self.tokens.pop();
+            true
+        } else {
+            false
+        }
+    }
+
+    fn expect(&mut self, text: &str) -> Result<Token, AsmError> {
+        let token = self.next(&format!("`{text}`"))?;
+        if token.text != text {
+            return Err(token.loc.error(format!("expected `{text}`, found `{}`", token.text)));
+        }
+        Ok(token)
+    }
+
+    fn emit(&mut self, byte: u8, loc: &Loc) -> Result<(), AsmError> {
+        if self.here >= RAM_SIZE {
+            return Err(loc.error("program does not fit in memory"));
+        }
+        self.rom[self.here] = byte;
+        self.here += 1;
+        self.end = self.end.max(self.here);
+        Ok(())
+    }
+
+    fn inst(&mut self, hi: u8, lo: u8, loc: &Loc) -> Result<(), AsmError> {
+        self.emit(hi, loc)?;
+        self.emit(lo, loc)
+    }
+
+    fn patch(&mut self, addr: usize, value: usize, kind: Fixup) {
+        let [hi, lo] = u16::try_from(value & 0xffff).unwrap().to_be_bytes();
+        match kind {
+            Fixup::Addr12 => {
+                self.rom[addr] = (self.rom[addr] & 0xf0) | (hi & 0x0f);
+                self.rom[addr + 1] = lo;
+            }
+            Fixup::Addr16 => {
+                self.rom[addr] = hi;
+                self.rom[addr + 1] = lo;
+            }
+            Fixup::Unpack => {
+                self.rom[addr + 1] = (self.rom[addr + 1] & 0xf0) | (hi & 0x0f);
+                self.rom[addr + 3] = lo;
+            }
+        }
+    }
+
+    fn register(&self, text: &str) -> Option<u8> {
+        if let Some(x) = self.aliases.get(text) {
+            return Some(*x);
+        }
+        let lower = text.to_ascii_lowercase();
+        lower.strip_prefix('v')
+            .filter(|n| n.len() == 1)
+            .and_then(|n| u8::from_str_radix(n, 16).ok())
+    }
+
+    fn expect_register(&mut self) -> Result<u8, AsmError> {
+        let token = self.next("a register")?;
+        self.register(&token.text).ok_or_else(|| token.loc.error(format!("expected a register, found `{}`", token.text)))
+    }
+
+    fn define_name(&self, token: &Token) -> Result<(), AsmError> {
+        if !is_name(&token.text) || self.register(&token.text).is_some() {
+            return Err(token.loc.error(format!("`{}` is not a valid name", token.text)));
+        }
+        if self.labels.contains_key(&token.text) || self.constants.contains_key(&token.text) {
+            return Err(token.loc.error(format!("`{}` is already defined", token.text)));
+        }
+        Ok(())
+    }
+
+    fn lookup(&self, text: &str) -> Option<f64> {
+        parse_number(text)
+            .or_else(|| self.constants.get(text).copied())
+            .or_else(|| self.labels.get(text).map(|addr| f64::from(u32::try_from(*addr).unwrap())))
+    }
+
+    // A number, constant or label that must already be defined.
+    #[allow(clippy::cast_possible_truncation)]
+    fn value(&self, token: &Token, min: i64, max: i64) -> Result<i64, AsmError> {
+        let value = self.lookup(&token.text)
+            .ok_or_else(|| token.loc.error(format!("undefined name `{}`", token.text)))?
+            .floor() as i64;
+        if !(min..=max).contains(&value) {
+            return Err(token.loc.error(format!("{value} is out of range {min}..={max}")));
+        }
+        Ok(value)
+    }
+
+    fn byte(&self, token: &Token) -> Result<u8, AsmError> {
+        Ok(self.value(token, -128, 0xff)?.to_be_bytes()[7])
+    }
+
+    fn nibble(&self, token: &Token) -> Result<u8, AsmError> {
+        Ok(self.value(token, 0, 0xf)?.to_be_bytes()[7])
+    }
+
+    // An address that may be a label defined further on, patched in by
+    // `kind` at `addr` once known.
+    fn target(&mut self, token: &Token, addr: usize, kind: Fixup) -> Result<usize, AsmError> {
+        let max = if matches!(kind, Fixup::Addr16) { 0xffff } else { 0xfff };
+        if self.lookup(&token.text).is_some() {
+            return Ok(usize::try_from(self.value(token, 0, max)?).unwrap());
+        }
+        if !is_name(&token.text) {
+            return Err(token.loc.error(format!("expected an address, found `{}`", token.text)));
+        }
+        self.fixups.push(Pending { name: token.text.clone(), addr, kind, loc: token.loc.clone() });
+        Ok(0)
+    }
+
+    // `1nnn`-style instructions: `hi` is the opcode nibble.
+    fn addr_inst(&mut self, hi: u8, token: &Token) -> Result<(), AsmError> {
+        let nnn = self.target(token, self.here, Fixup::Addr12)?;
+        let [n_hi, n_lo] = u16::try_from(nnn).unwrap().to_be_bytes();
+        self.inst(hi << 4 | n_hi, n_lo, &token.loc)
+    }
+
+    // A jump to be patched later; returns its address.
+    fn placeholder(&mut self, loc: &Loc) -> Result<usize, AsmError> {
+        let addr = self.here;
+        self.inst(0x10, 0x00, loc)?;
+        Ok(addr)
+    }
+
+    fn compile(&mut self) -> Result<Vec<u8>, AsmError> {
+        while let Some(token) = self.tokens.pop() {
+            self.start(&token)?;
+            self.statement(&token)?;
+        }
+
+        if let Some((_, _, loc)) = self.loops.last() {
+            return Err(loc.error("`loop` without `again`"));
+        }
+        if let Some((_, loc)) = self.branches.last() {
+            return Err(loc.error("`begin` without `end`"));
+        }
+
+        for fixup in std::mem::take(&mut self.fixups) {
+            let Some(&addr) = self.labels.get(&fixup.name) else {
+                if fixup.name == "main" && self.main_jump == Some(true) {
+                    return Err(fixup.loc.error("program has no `: main`"));
+                }
+                return Err(fixup.loc.error(format!("undefined name `{}`", fixup.name)));
+            };
+            if !matches!(fixup.kind, Fixup::Addr16) && addr > 0xfff {
+                return Err(fixup.loc.error(format!("`{}` is above 0xfff; use `i := long`", fixup.name)));
+            }
+            self.patch(fixup.addr, addr, fixup.kind);
+        }
+
+        Ok(self.rom[PROGRAM_BASE..self.end].to_vec())
+    }
+
+    // Execution starts at 0x200: unless `: main` is the first thing laid out
+    // there, a jump to it goes first. Decided by the first statement that is
+    // not just a definition.
+    fn start(&mut self, token: &Token) -> Result<(), AsmError> {
+        if self.main_jump.is_some() || matches!(token.text.as_str(), ":const" | ":calc" | ":alias" | ":macro") {
+            return Ok(());
+        }
+
+        let main_first = token.text == ":" && self.tokens.last().is_some_and(|name| name.text == "main");
+        self.main_jump = Some(!main_first);
+        if !main_first {
+            self.fixups.push(Pending { name: "main".into(), addr: self.here, kind: Fixup::Addr12, loc: token.loc.clone() });
+            self.placeholder(&token.loc)?;
+        }
+        Ok(())
+    }
+
+    #[allow(clippy::too_many_lines)]
+    fn statement(&mut self, token: &Token) -> Result<(), AsmError> {
+        let loc = token.loc.clone();
+        if let Some(x) = self.register(&token.text) {
+            return self.assignment(x, &loc);
+        }
+
+        match token.text.as_str() {
+            ":" => {
+                let name = self.next("a label name")?;
+                self.define_name(&name)?;
+                self.labels.insert(name.text, self.here);
+            }
+            ":alias" => {
+                let name = self.next("an alias name")?;
+                let x = self.expect_register()?;
+                self.aliases.insert(name.text, x);
+            }
+            ":const" => {
+                let name = self.next("a constant name")?;
+                self.define_name(&name)?;
+                let value = self.next("a value")?;
+                let value = self.lookup(&value.text).ok_or_else(|| value.loc.error(format!("undefined name `{}`", value.text)))?;
+                self.constants.insert(name.text, value);
+            }
+            ":calc" => {
+                let name = self.next("a constant name")?;
+                self.define_name(&name)?;
+                let value = self.calc()?;
+                self.constants.insert(name.text, value);
+            }
+            ":byte" => {
+                let value = if self.tokens.last().is_some_and(|token| token.text == "{") {
+                    let value = self.calc()?;
+                    let token = Token { text: value.to_string(), loc: loc.clone() };
+                    self.byte(&token)?
+                } else {
+                    let token = self.next("a value")?;
+                    self.byte(&token)?
+                };
+                self.emit(value, &loc)?;
+            }
+            ":org" => {
+                let addr = self.next("an address")?;
+                let addr = usize::try_from(self.value(&addr, 0, 0xffff)?).unwrap();
+                if addr < PROGRAM_BASE {
+                    return Err(loc.error(format!("`:org` below {PROGRAM_BASE:#05x}")));
+                }
+                self.here = addr;
+            }
+            ":unpack" => {
+                let hi = self.next("a nibble")?;
+                let hi = self.nibble(&hi)?;
+                let label = self.next("a label")?;
+                let addr = self.target(&label, self.here, Fixup::Unpack)?;
+                let [a_hi, a_lo] = u16::try_from(addr).unwrap().to_be_bytes();
+                self.inst(0x60, hi << 4 | a_hi, &loc)?;
+                self.inst(0x61, a_lo, &loc)?;
+            }
+            ":macro" => self.define_macro()?,
+            ":call" => {
+                let target = self.next("an address")?;
+                self.addr_inst(0x2, &target)?;
+            }
+            ":breakpoint" => {
+                self.next("a breakpoint name")?;
+            }
+            ":monitor" => {
+                self.next("an address")?;
+                self.next("a length or format")?;
+            }
+            "clear"        => self.inst(0x00, 0xe0, &loc)?,
+            "return" | ";" => self.inst(0x00, 0xee, &loc)?,
+            "scroll-right" => self.inst(0x00, 0xfb, &loc)?,
+            "scroll-left"  => self.inst(0x00, 0xfc, &loc)?,
+            "exit"         => self.inst(0x00, 0xfd, &loc)?,
+            "lores"        => self.inst(0x00, 0xfe, &loc)?,
+            "hires"        => self.inst(0x00, 0xff, &loc)?,
+            "audio"        => self.inst(0xf0, 0x02, &loc)?,
+            "scroll-down" | "scroll-up" | "plane" => {
+                let n = self.next("a nibble")?;
+                let n = self.nibble(&n)?;
+                match token.text.as_str() {
+                    "scroll-down" => self.inst(0x00, 0xc0 | n, &loc)?,
+                    "scroll-up"   => self.inst(0x00, 0xd0 | n, &loc)?,
+                    _             => self.inst(0xf0 | n, 0x01, &loc)?,
+                }
+            }
+            "jump" | "jump0" | "native" => {
+                let target = self.next("an address")?;
+                let hi = match token.text.as_str() {
+                    "jump"  => 0x1,
+                    "jump0" => 0xb,
+                    _       => 0x0,
+                };
+                self.addr_inst(hi, &target)?;
+            }
+            "sprite" => {
+                let x = self.expect_register()?;
+                let y = self.expect_register()?;
+                let n = self.next("a sprite height")?;
+                let n = self.nibble(&n)?;
+                self.inst(0xd0 | x, y << 4 | n, &loc)?;
+            }
+            "bcd" | "saveflags" | "loadflags" => {
+                let x = self.expect_register()?;
+                let lo = match token.text.as_str() {
+                    "bcd"       => 0x33,
+                    "saveflags" => 0x75,
+                    _           => 0x85,
+                };
+                self.inst(0xf0 | x, lo, &loc)?;
+            }
+            "save" | "load" => {
+                let x = self.expect_register()?;
+                let save = token.text == "save";
+                if self.eat("-") {
+                    let y = self.expect_register()?;
+                    self.inst(0x50 | x, y << 4 | if save { 0x2 } else { 0x3 }, &loc)?;
+                } else {
+                    self.inst(0xf0 | x, if save { 0x55 } else { 0x65 }, &loc)?;
+                }
+            }
+            "delay" | "buzzer" | "pitch" => {
+                self.expect(":=")?;
+                let x = self.expect_register()?;
+                let lo = match token.text.as_str() {
+                    "delay"  => 0x15,
+                    "buzzer" => 0x18,
+                    _        => 0x3a,
+                };
+                self.inst(0xf0 | x, lo, &loc)?;
+            }
+            "i" => self.i(&loc)?,
+            "if" => {
+                let condition = self.condition()?;
+                let then = self.next("`then` or `begin`")?;
+                match then.text.as_str() {
+                    "then" => self.emit_condition(&condition, false)?,
+                    "begin" => {
+                        self.emit_condition(&condition, true)?;
+                        let jump = self.placeholder(&loc)?;
+                        self.branches.push((jump, loc));
+                    }
+                    _ => return Err(then.loc.error(format!("expected `then` or `begin`, found `{}`", then.text))),
+                }
+            }
+            "else" => {
+                let (jump, _) = self.branches.pop().ok_or_else(|| loc.error("`else` without `begin`"))?;
+                let skip = self.placeholder(&loc)?;
+                self.patch(jump, self.here, Fixup::Addr12);
+                self.branches.push((skip, loc));
+            }
+            "end" => {
+                let (jump, _) = self.branches.pop().ok_or_else(|| loc.error("`end` without `begin`"))?;
+                self.patch(jump, self.here, Fixup::Addr12);
+            }
+            "loop" => self.loops.push((self.here, Vec::new(), loc)),
+            "while" => {
+                if self.loops.is_empty() {
+                    return Err(loc.error("`while` outside a loop"));
+                }
+                let condition = self.condition()?;
+                self.emit_condition(&condition, true)?;
+                let jump = self.placeholder(&loc)?;
+                self.loops.last_mut().unwrap().1.push(jump);
+            }
+            "again" => {
+                let (start, exits, _) = self.loops.pop().ok_or_else(|| loc.error("`again` without `loop`"))?;
+                let jump = self.placeholder(&loc)?;
+                self.patch(jump, start, Fixup::Addr12);
+                for exit in exits {
+                    self.patch(exit, self.here, Fixup::Addr12);
+                }
+            }
+            name if self.macros.contains_key(name) => self.expand(token)?,
+            name if parse_number(name).is_some() => {
+                let byte = self.byte(token)?;
+                self.emit(byte, &loc)?;
+            }
+            name if is_name(name) => self.addr_inst(0x2, token)?,
+            _ => return Err(loc.error(format!("unexpected `{}`", token.text))),
+        }
+
+        Ok(())
+    }
+
+    fn assignment(&mut self, x: u8, loc: &Loc) -> Result<(), AsmError> {
+        let op = self.next("an operator")?;
+        let rhs = self.next("a register or value")?;
+        let y = self.register(&rhs.text);
+
+        let alu = match (op.text.as_str(), y) {
+            (":=", Some(_))  => Some(0x0),
+            ("|=", Some(_))  => Some(0x1),
+            ("&=", Some(_))  => Some(0x2),
+            ("^=", Some(_))  => Some(0x3),
+            ("+=", Some(_))  => Some(0x4),
+            ("-=", Some(_))  => Some(0x5),
+            (">>=", Some(_)) => Some(0x6),
+            ("=-", Some(_))  => Some(0x7),
+            ("<<=", Some(_)) => Some(0xe),
+            _ => None,
+        };
+        if let (Some(alu), Some(y)) = (alu, y) {
+            return self.inst(0x80 | x, y << 4 | alu, loc);
+        }
+
+        match (op.text.as_str(), rhs.text.as_str()) {
+            (":=", "key")   => self.inst(0xf0 | x, 0x0a, loc),
+            (":=", "delay") => self.inst(0xf0 | x, 0x07, loc),
+            (":=", "random") => {
+                let mask = self.next("a mask")?;
+                let mask = self.byte(&mask)?;
+                self.inst(0xc0 | x, mask, loc)
+            }
+            (":=", _) => {
+                let nn = self.byte(&rhs)?;
+                self.inst(0x60 | x, nn, loc)
+            }
+            ("+=", _) => {
+                let nn = self.byte(&rhs)?;
+                self.inst(0x70 | x, nn, loc)
+            }
+            ("-=", _) => {
+                let nn = self.byte(&rhs)?;
+                self.inst(0x70 | x, nn.wrapping_neg(), loc)
+            }
+            _ => Err(op.loc.error(format!("unexpected `{} {}` after a register", op.text, rhs.text))),
+        }
+    }
+
+    fn i(&mut self, loc: &Loc) -> Result<(), AsmError> {
+        let op = self.next("`:=` or `+=`")?;
+        match op.text.as_str() {
+            "+=" => {
+                let x = self.expect_register()?;
+                self.inst(0xf0 | x, 0x1e, loc)
+            }
+            ":=" => {
+                let rhs = self.next("an address")?;
+                match rhs.text.as_str() {
+                    "hex" | "bighex" => {
+                        let x = self.expect_register()?;
+                        self.inst(0xf0 | x, if rhs.text == "hex" { 0x29 } else { 0x30 }, loc)
+                    }
+                    "long" => {
+                        let target = self.next("an address")?;
+                        let nnnn = self.target(&target, self.here + 2, Fixup::Addr16)?;
+                        let [hi, lo] = u16::try_from(nnnn).unwrap().to_be_bytes();
+                        self.inst(0xf0, 0x00, loc)?;
+                        self.inst(hi, lo, loc)
+                    }
+                    _ => self.addr_inst(0xa, &rhs),
+                }
+            }
+            _ => Err(op.loc.error(format!("expected `:=` or `+=` after `i`, found `{}`", op.text))),
+        }
+    }
+
+    fn condition(&mut self) -> Result<Condition, AsmError> {
+        let x = self.expect_register()?;
+        let op = self.next("a comparison")?;
+        let rhs = match op.text.as_str() {
+            "key" | "-key" => None,
+            "==" | "!=" | "<" | ">" | "<=" | ">=" => Some(self.next("a register or value")?),
+            _ => return Err(op.loc.error(format!("expected a comparison, found `{}`", op.text))),
+        };
+        Ok(Condition { x, op, rhs })
+    }
+
+    // Emits the instruction(s) that skip the next one unless `condition`
+    // holds, or when `negated`, skip it if it does. `<`, `>`, `<=` and `>=`
+    // go through vf.
+    fn emit_condition(&mut self, condition: &Condition, negated: bool) -> Result<(), AsmError> {
+        let Condition { x, op, rhs } = condition;
+        let loc = &op.loc;
+        let Some(rhs) = rhs else {
+            let pressed = (op.text == "key") != negated;
+            return self.inst(0xe0 | x, if pressed { 0xa1 } else { 0x9e }, loc);
+        };
+        let y = self.register(&rhs.text);
+
+        if op.text == "==" || op.text == "!=" {
+            let skip_if_equal = (op.text == "!=") != negated;
+            if let Some(y) = y {
+                return self.inst(if skip_if_equal { 0x50 } else { 0x90 } | x, y << 4, loc);
+            }
+            let nn = self.byte(rhs)?;
+            return self.inst(if skip_if_equal { 0x30 } else { 0x40 } | x, nn, loc);
+        }
+
+        if let Some(y) = y {
+            self.inst(0x8f, y << 4, loc)?;
+        } else {
+            let nn = self.byte(rhs)?;
+            self.inst(0x6f, nn, loc)?;
+        }
+        let subtract = if op.text == ">" || op.text == "<=" { 0x5 } else { 0x7 };
+        self.inst(0x8f, x << 4 | subtract, loc)?;
+        // vf is now 1 exactly when `<=` or `>=` holds. Negating turns `<`
+        // into `>=` and so on, which keeps the subtraction; like Octo, skip
+        // with `3F 01` for the strict forms and `4F 01` for the others.
+        let strict = (op.text == "<" || op.text == ">") != negated;
+        self.inst(if strict { 0x3f } else { 0x4f }, 0x01, loc)
+    }
+
+    fn define_macro(&mut self) -> Result<(), AsmError> {
+        let name = self.next("a macro name")?;
+        if !is_name(&name.text) {
+            return Err(name.loc.error(format!("`{}` is not a valid name", name.text)));
+        }
+        let mut args = Vec::new();
+        loop {
+            let arg = self.next("`{`")?;
+            if arg.text == "{" {
+                break;
+            }
+            args.push(arg.text);
+        }
+        let body = self.braced(&name.loc)?;
+        self.macros.insert(name.text, Macro { args, body });
+        Ok(())
+    }
+
+    // Tokens up to the `}` matching an already consumed `{`.
+    fn braced(&mut self, open: &Loc) -> Result<Vec<Token>, AsmError> {
+        let mut body = Vec::new();
+        let mut depth = 0;
+        loop {
+            let token = self.tokens.pop().ok_or_else(|| open.error("`{` without `}`"))?;
+            match token.text.as_str() {
+                "{" => depth += 1,
+                "}" if depth == 0 => return Ok(body),
+                "}" => depth -= 1,
+                _ => {}
+            }
+            body.push(token);
+        }
+    }
+
+    fn expand(&mut self, call: &Token) -> Result<(), AsmError> {
+        let arg_count = self.macros[&call.text].args.len();
+        let mut values = HashMap::new();
+        for n in 0..arg_count {
+            let value = self.next("a macro argument")?;
+            values.insert(self.macros[&call.text].args[n].clone(), value.text);
+        }
+
+        let body = &self.macros[&call.text].body;
+        self.expanded += body.len();
+        if self.expanded > MAX_EXPANSION {
+            return Err(call.loc.error(format!("macro `{}` expands forever", call.text)));
+        }
+        let expansion = body.iter().rev().map(|token| Token {
+            text: values.get(&token.text).unwrap_or(&token.text).clone(),
+            loc: token.loc.clone(),
+        }).collect::<Vec<_>>();
+        self.tokens.extend(expansion);
+        Ok(())
+    }
+
+    fn calc(&mut self) -> Result<f64, AsmError> {
+        let open = self.expect("{")?;
+        let tokens = self.braced(&open.loc)?;
+        let mut pos = 0;
+        let value = self.calc_expr(&tokens, &mut pos, &open.loc)?;
+        if let Some(token) = tokens.get(pos) {
+            return Err(token.loc.error(format!("unexpected `{}` in expression", token.text)));
+        }
+        Ok(value)
+    }
+
+    // Octo evaluates binary operators right to left with no precedence.
+    #[allow(clippy::cast_possible_truncation, clippy::cast_precision_loss)]
+    fn calc_expr(&self, tokens: &[Token], pos: &mut usize, open: &Loc) -> Result<f64, AsmError> {
+        let lhs = self.calc_term(tokens, pos, open)?;
+        let Some(op) = tokens.get(*pos) else {
+            return Ok(lhs);
+        };
+        if op.text == ")" {
+            return Ok(lhs);
+        }
+        *pos += 1;
+        let rhs = self.calc_expr(tokens, pos, open)?;
+
+        let (a, b) = (lhs as i64, rhs as i64);
+        let shift = |f: fn(i64, u32) -> Option<i64>| {
+            u32::try_from(b).ok().and_then(|b| f(a, b)).map(|v| v as f64)
+                .ok_or_else(|| op.loc.error(format!("cannot shift by {b}")))
+        };
+        Ok(match op.text.as_str() {
+            "+"   => lhs + rhs,
+            "-"   => lhs - rhs,
+            "*"   => lhs * rhs,
+            "/"   => lhs / rhs,
+            "%"   => lhs % rhs,
+            "pow" => lhs.powf(rhs),
+            "min" => lhs.min(rhs),
+            "max" => lhs.max(rhs),
+            "&"   => (a & b) as f64,
+            "|"   => (a | b) as f64,
+            "^"   => (a ^ b) as f64,
+            "<<"  => shift(i64::checked_shl)?,
+            ">>"  => shift(i64::checked_shr)?,
+            "<"   => f64::from(u8::from(lhs < rhs)),
+            ">"   => f64::from(u8::from(lhs > rhs)),
+            "<="  => f64::from(u8::from(lhs <= rhs)),
+            ">="  => f64::from(u8::from(lhs >= rhs)),
+            "=="  => f64::from(u8::from(a == b)),
+            "!="  => f64::from(u8::from(a != b)),
+            _ => return Err(op.loc.error(format!("unknown operator `{}`", op.text))),
+        })
+    }
+
+    #[allow(clippy::cast_possible_truncation, clippy::cast_precision_loss, clippy::cast_sign_loss)]
+    fn calc_term(&self, tokens: &[Token], pos: &mut usize, open: &Loc) -> Result<f64, AsmError> {
+        let token = tokens.get(*pos).ok_or_else(|| open.error("expression ends early"))?;
+        *pos += 1;
+
+        let unary: Option<fn(f64) -> f64> = match token.text.as_str() {
+            "-"     => Some(|a| -a),
+            "~"     => Some(|a| !(a as i64) as f64),
+            "!"     => Some(|a| f64::from(u8::from(a == 0.0))),
+            "abs"   => Some(f64::abs),
+            "sqrt"  => Some(f64::sqrt),
+            "sin"   => Some(f64::sin),
+            "cos"   => Some(f64::cos),
+            "tan"   => Some(f64::tan),
+            "exp"   => Some(f64::exp),
+            "log"   => Some(f64::ln),
+            "sign"  => Some(f64::signum),
+            "ceil"  => Some(f64::ceil),
+            "floor" => Some(f64::floor),
+            _ => None,
+        };
+        if let Some(unary) = unary {
+            return Ok(unary(self.calc_term(tokens, pos, open)?));
+        }
+
+        match token.text.as_str() {
+            "(" => {
+                let value = self.calc_expr(tokens, pos, open)?;
+                match tokens.get(*pos) {
+                    Some(close) if close.text == ")" => {
+                        *pos += 1;
+                        Ok(value)
+                    }
+                    _ => Err(token.loc.error("`(` without `)`")),
+                }
+            }
+            "@" => {
+                let addr = self.calc_term(tokens, pos, open)?;
+                let byte = self.rom.get(addr as usize).copied().unwrap_or(0);
+                Ok(f64::from(byte))
+            }
+            "HERE" => Ok(self.here as f64),
+            "PI" => Ok(PI),
+            "E" => Ok(E),
+            text => self.lookup(text).ok_or_else(|| token.loc.error(format!("undefined name `{text}`"))),
+        }
+    }
+}
+
+// Compiles Octo source: labels, `:=`-style assignment, `if ... then` and
+// `if ... begin ... else ... end`, `loop`/`while`/`again`, `:macro`, `:calc`,
+// `:const`, `:alias`, `:org`, `:byte` and `:unpack`, with the SCHIP and
+// XO-CHIP instructions. The ROM is laid out from `PROGRAM_BASE`.
+pub fn compile_octo(source: &str) -> Result<Vec<u8>, AsmError> {
+    Octo::new(&"<source>".into(), source).compile()
+}
+
+pub fn compile_octo_file(path: &str) -> Result<Vec<u8>, AsmError> {
+    let path = PathBuf::from(path);
+    let text = read_source(&path)?;
+    Octo::new(&Path::new(&path).to_string_lossy().into(), &text).compile()
+}
@@ -1,5 +1,7 @@
+use std::sync::OnceLock;
 
-#[allow(non_camel_case_types)]
+#[allow(non_camel_case_types, clippy::upper_case_acronyms)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum Op {
     LD_Vx_byte {
         // 6xkk
@@ -153,47 +155,345 @@ pub enum Op {
         // Fx65
         x: usize,
     },
+    SCD_nibble {
+        // 00Cn
+        n: u8
+    },
+    SCU_nibble {
+        // 00Dn
+        n: u8
+    },
+    SCR {
+        // 00FB
+    },
+    SCL {
+        // 00FC
+    },
+    EXIT {
+        // 00FD
+    },
+    LOW {
+        // 00FE
+    },
+    HIGH {
+        // 00FF
+    },
+    LD_HF_Vx {
+        // Fx30
+        x: usize,
+    },
+    LD_R_Vx {
+        // Fx75
+        x: usize,
+    },
+    LD_Vx_R {
+        // Fx85
+        x: usize,
+    },
+    LD_I_long {
+        // F000 nnnn
+        nnnn: usize
+    },
+    SAVE_Vx_Vy {
+        // 5xy2
+        x: usize,
+        y: usize
+    },
+    LOAD_Vx_Vy {
+        // 5xy3
+        x: usize,
+        y: usize
+    },
+    PLANE_n {
+        // Fn01
+        n: u8
+    },
+    AUDIO {
+        // F002
+    },
+    PITCH_Vx {
+        // Fx3A
+        x: usize,
+    },
     UNKNOWN {
     }
 }
 
+// Every word decoded, built on first use: about 1.5 MiB.
+static DECODE_TABLE: OnceLock<Box<[Op]>> = OnceLock::new();
+
+impl Op {
+    // `F000 nnnn` decodes with `nnnn` 0, its address being in the next
+    // word; see `from_bytes`. Words that are no instruction are `UNKNOWN`.
+    #[must_use]
+    pub fn decode(word: u16) -> Op {
+        let table = DECODE_TABLE.get_or_init(|| (0..=u16::MAX).map(Op::decode_uncached).collect());
+        table[usize::from(word)]
+    }
+
+    // The same as `decode` without the table, which it builds.
+    #[must_use]
+    #[allow(clippy::too_many_lines)]
+    pub fn decode_uncached(word: u16) -> Op {
+        let x =  ((word & 0x0f00) >> 8) as usize;
+        let y =  ((word & 0x00f0) >> 4) as usize;
+        let n =   (word & 0x000f) as u8;
+        let nn =  (word & 0x00ff) as u8;
+        let nnn = (word & 0x0fff) as usize;
+
+        if word & 0xf000 == 0x6000 {
+            Op::LD_Vx_byte { x, nn }
+        } else if word & 0xf000 == 0x7000 {
+            Op::ADD_Vx_byte { x, nn }
+        } else if word & 0xf00f == 0x8000 {
+            Op::LD_Vx_Vy { x, y }
+        } else if word & 0xf00f == 0x8004 {
+            Op::ADD_Vx_Vy { x, y }
+        } else if word & 0xf00f == 0x8005 {
+            Op::SUB_Vx_Vy { x, y }
+        } else if word & 0xf00f == 0x8007 {
+            Op::SUBN_Vx_Vy { x, y }
+        } else if word & 0xf00f == 0x8002 {
+            Op::AND_Vx_Vy { x, y }
+        } else if word & 0xf00f == 0x8001 {
+            Op::OR_Vx_Vy { x, y }
+        } else if word & 0xf00f == 0x8003 {
+            Op::XOR_Vx_Vy { x, y }
+        } else if word & 0xf00f == 0x8006 {
+            Op::SHR_Vx_Vy { x, y }
+        } else if word & 0xf00f == 0x800e {
+            Op::SHL_Vx_Vy { x, y }
+        } else if word & 0xf000 == 0xc000 {
+            Op::RND_Vx_byte { x, nn }
+        } else if word & 0xf000 == 0x1000 {
+            Op::JP_addr { nnn }
+        } else if word & 0xf000 == 0xb000 {
+            Op::JP_V0_addr { nnn }
+        } else if word & 0xf000 == 0x2000 {
+            Op::CALL_addr { nnn }
+        } else if word == 0x00ee {
+            Op::RET {}
+        } else if word & 0xf000 == 0x3000 {
+            Op::SE_Vx_byte { x, nn }
+        } else if word & 0xf00f == 0x5000 {
+            Op::SE_Vx_Vy { x, y }
+        } else if word & 0xf00f == 0x5002 {
+            Op::SAVE_Vx_Vy { x, y }
+        } else if word & 0xf00f == 0x5003 {
+            Op::LOAD_Vx_Vy { x, y }
+        } else if word & 0xf000 == 0x4000 {
+            Op::SNE_Vx_byte { x, nn }
+        } else if word & 0xf00f == 0x9000 {
+            Op::SNE_Vx_Vy { x, y }
+        } else if word & 0xf0ff == 0xf015 {
+            Op::LD_DT_Vx { x }
+        } else if word & 0xf0ff == 0xf007 {
+            Op::LD_Vx_DT { x }
+        } else if word & 0xf0ff == 0xf018 {
+            Op::LD_ST_Vx { x }
+        } else if word & 0xf0ff == 0xf00a {
+            Op::LD_Vx_K { x }
+        } else if word & 0xf0ff == 0xe09e {
+            Op::SKP_Vx { x }
+        } else if word & 0xf0ff == 0xe0a1 {
+            Op::SKNP_Vx { x }
+        } else if word & 0xf000 == 0xa000 {
+            Op::LD_I_addr { nnn }
+        } else if word & 0xf0ff == 0xf01e {
+            Op::ADD_I_Vx { x }
+        } else if word & 0xf000 == 0xd000 {
+            Op::DRW_Vx_Vy_nibble { x, y, n }
+        } else if word == 0x00e0 {
+            Op::CLS {}
+        } else if word & 0xfff0 == 0x00c0 {
+            Op::SCD_nibble { n }
+        } else if word & 0xfff0 == 0x00d0 {
+            Op::SCU_nibble { n }
+        } else if word == 0x00fb {
+            Op::SCR {}
+        } else if word == 0x00fc {
+            Op::SCL {}
+        } else if word == 0x00fd {
+            Op::EXIT {}
+        } else if word == 0x00fe {
+            Op::LOW {}
+        } else if word == 0x00ff {
+            Op::HIGH {}
+        } else if word & 0xf0ff == 0xf030 {
+            Op::LD_HF_Vx { x }
+        } else if word & 0xf0ff == 0xf075 {
+            Op::LD_R_Vx { x }
+        } else if word & 0xf0ff == 0xf085 {
+            Op::LD_Vx_R { x }
+        } else if word == 0xf000 {
+            Op::LD_I_long { nnnn: 0 }
+        } else if word == 0xf002 {
+            Op::AUDIO {}
+        } else if word & 0xf0ff == 0xf001 {
+            #[allow(clippy::cast_possible_truncation)]
+            Op::PLANE_n { n: x as u8 }
+        } else if word & 0xf0ff == 0xf03a {
+            Op::PITCH_Vx { x }
+        } else if word & 0xf0ff == 0xf029 {
+            Op::LD_F_Vx { x }
+        } else if word & 0xf0ff == 0xf033 {
+            Op::LD_B_Vx { x }
+        } else if word & 0xf0ff == 0xf055 {
+            Op::LD_I_Vx { x }
+        } else if word & 0xf0ff == 0xf065 {
+            Op::LD_Vx_I { x }
+        } else {
+            Op::UNKNOWN {}
+        }
+    }
+
+    // The first word of the instruction, fields masked to their width.
+    // `UNKNOWN` encodes as 0x0000, which decodes back to it.
+    #[must_use]
+    pub fn encode(&self) -> u16 {
+        let nib = |v: usize| u16::try_from(v & 0xf).unwrap();
+        let xy = |base: u16, x: usize, y: usize| base | nib(x) << 8 | nib(y) << 4;
+        let x_nn = |base: u16, x: usize, nn: u8| base | nib(x) << 8 | u16::from(nn);
+        let addr = |base: u16, nnn: usize| base | u16::try_from(nnn & 0xfff).unwrap();
+        match *self {
+            Op::LD_Vx_byte { x, nn }         => x_nn(0x6000, x, nn),
+            Op::ADD_Vx_byte { x, nn }        => x_nn(0x7000, x, nn),
+            Op::LD_Vx_Vy { x, y }            => xy(0x8000, x, y),
+            Op::ADD_Vx_Vy { x, y }           => xy(0x8004, x, y),
+            Op::SUB_Vx_Vy { x, y }           => xy(0x8005, x, y),
+            Op::SUBN_Vx_Vy { x, y }          => xy(0x8007, x, y),
+            Op::AND_Vx_Vy { x, y }           => xy(0x8002, x, y),
+            Op::OR_Vx_Vy { x, y }            => xy(0x8001, x, y),
+            Op::XOR_Vx_Vy { x, y }           => xy(0x8003, x, y),
+            Op::SHR_Vx_Vy { x, y }           => xy(0x8006, x, y),
+            Op::SHL_Vx_Vy { x, y }           => xy(0x800e, x, y),
+            Op::RND_Vx_byte { x, nn }        => x_nn(0xc000, x, nn),
+            Op::JP_addr { nnn }              => addr(0x1000, nnn),
+            Op::JP_V0_addr { nnn }           => addr(0xb000, nnn),
+            Op::CALL_addr { nnn }            => addr(0x2000, nnn),
+            Op::RET {}                       => 0x00ee,
+            Op::SE_Vx_byte { x, nn }         => x_nn(0x3000, x, nn),
+            Op::SE_Vx_Vy { x, y }            => xy(0x5000, x, y),
+            Op::SNE_Vx_byte { x, nn }        => x_nn(0x4000, x, nn),
+            Op::SNE_Vx_Vy { x, y }           => xy(0x9000, x, y),
+            Op::LD_DT_Vx { x }               => x_nn(0xf000, x, 0x15),
+            Op::LD_Vx_DT { x }               => x_nn(0xf000, x, 0x07),
+            Op::LD_ST_Vx { x }               => x_nn(0xf000, x, 0x18),
+            Op::LD_Vx_K { x }                => x_nn(0xf000, x, 0x0a),
+            Op::SKP_Vx { x }                 => x_nn(0xe000, x, 0x9e),
+            Op::SKNP_Vx { x }                => x_nn(0xe000, x, 0xa1),
+            Op::LD_I_addr { nnn }            => addr(0xa000, nnn),
+            Op::ADD_I_Vx { x }               => x_nn(0xf000, x, 0x1e),
+            Op::DRW_Vx_Vy_nibble { x, y, n } => xy(0xd000, x, y) | u16::from(n & 0xf),
+            Op::CLS {}                       => 0x00e0,
+            Op::LD_F_Vx { x }                => x_nn(0xf000, x, 0x29),
+            Op::LD_B_Vx { x }                => x_nn(0xf000, x, 0x33),
+            Op::LD_I_Vx { x }                => x_nn(0xf000, x, 0x55),
+            Op::LD_Vx_I { x }                => x_nn(0xf000, x, 0x65),
+            Op::SCD_nibble { n }             => 0x00c0 | u16::from(n & 0xf),
+            Op::SCU_nibble { n }             => 0x00d0 | u16::from(n & 0xf),
+            Op::SCR {}                       => 0x00fb,
+            Op::SCL {}                       => 0x00fc,
+            Op::EXIT {}                      => 0x00fd,
+            Op::LOW {}                       => 0x00fe,
+            Op::HIGH {}                      => 0x00ff,
+            Op::LD_HF_Vx { x }               => x_nn(0xf000, x, 0x30),
+            Op::LD_R_Vx { x }                => x_nn(0xf000, x, 0x75),
+            Op::LD_Vx_R { x }                => x_nn(0xf000, x, 0x85),
+            Op::LD_I_long { .. }             => 0xf000,
+            Op::SAVE_Vx_Vy { x, y }          => xy(0x5002, x, y),
+            Op::LOAD_Vx_Vy { x, y }          => xy(0x5003, x, y),
+            Op::PLANE_n { n }                => x_nn(0xf000, usize::from(n), 0x01),
+            Op::AUDIO {}                     => 0xf002,
+            Op::PITCH_Vx { x }               => x_nn(0xf000, x, 0x3a),
+            Op::UNKNOWN {}                   => 0x0000,
+        }
+    }
+
+    // Bytes the instruction takes: 4 for `F000 nnnn`, 2 for the rest.
+    #[must_use]
+    pub fn size(&self) -> usize {
+        match self {
+            Op::LD_I_long { .. } => 4,
+            _ => 2,
+        }
+    }
+
+    // The whole instruction, big-endian, `size()` bytes long.
+    #[must_use]
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut bytes = self.encode().to_be_bytes().to_vec();
+        if let Op::LD_I_long { nnnn } = self {
+            bytes.extend(u16::try_from(nnnn & 0xffff).unwrap().to_be_bytes());
+        }
+        bytes
+    }
+
+    // Decodes the instruction `bytes` starts with, reading the second word
+    // of `F000 nnnn`. `None` if `bytes` stops short of it.
+    #[must_use]
+    pub fn from_bytes(bytes: &[u8]) -> Option<Op> {
+        let word = |n: usize| bytes.get(n..n + 2).map(|w| u16::from_be_bytes([w[0], w[1]]));
+        match Op::decode(word(0)?) {
+            Op::LD_I_long { .. } => Some(Op::LD_I_long { nnnn: usize::from(word(2)?) }),
+            op => Some(op),
+        }
+    }
+}
+
 impl std::fmt::Display for Op {
     fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
         match self {
-            Op::LD_Vx_byte { x, nn }         => write!(f, "LD V{:x}, {:#04x}", x, nn),
-            Op::ADD_Vx_byte { x, nn }        => write!(f, "ADD V{:x}, {:#04x}", x, nn),
-            Op::LD_Vx_Vy { x, y }            => write!(f, "LD V{:x}, V{:x}", x, y),
-            Op::ADD_Vx_Vy { x, y }           => write!(f, "ADD V{:x}, V{:x}", x, y),
-            Op::SUB_Vx_Vy { x, y }           => write!(f, "SUB V{:x}, V{:x}", x, y),
-            Op::SUBN_Vx_Vy { x, y }          => write!(f, "SUBN V{:x}, V{:x}", x, y),
-            Op::AND_Vx_Vy { x, y }           => write!(f, "AND V{:x}, V{:x}", x, y),
-            Op::OR_Vx_Vy { x, y }            => write!(f, "OR V{:x}, V{:x}", x, y),
-            Op::XOR_Vx_Vy { x, y }           => write!(f, "XOR V{:x}, V{:x}", x, y),
-            Op::SHR_Vx_Vy { x, y }           => write!(f, "SHR V{:x}, V{:x}", x, y),
-            Op::SHL_Vx_Vy { x, y }           => write!(f, "SHL V{:x}, V{:x}", x, y),
-            Op::RND_Vx_byte { x, nn }        => write!(f, "RND V{:x}, {:#04x}", x, nn),
-            Op::JP_addr { nnn }              => write!(f, "JP {:#05x}", nnn),
-            Op::JP_V0_addr { nnn }           => write!(f, "JP V0, {:#05x}", nnn),
-            Op::CALL_addr { nnn }            => write!(f, "CALL {:#05x}", nnn),
+            Op::LD_Vx_byte { x, nn }         => write!(f, "LD V{x:x}, {nn:#04x}"),
+            Op::ADD_Vx_byte { x, nn }        => write!(f, "ADD V{x:x}, {nn:#04x}"),
+            Op::LD_Vx_Vy { x, y }            => write!(f, "LD V{x:x}, V{y:x}"),
+            Op::ADD_Vx_Vy { x, y }           => write!(f, "ADD V{x:x}, V{y:x}"),
+            Op::SUB_Vx_Vy { x, y }           => write!(f, "SUB V{x:x}, V{y:x}"),
+            Op::SUBN_Vx_Vy { x, y }          => write!(f, "SUBN V{x:x}, V{y:x}"),
+            Op::AND_Vx_Vy { x, y }           => write!(f, "AND V{x:x}, V{y:x}"),
+            Op::OR_Vx_Vy { x, y }            => write!(f, "OR V{x:x}, V{y:x}"),
+            Op::XOR_Vx_Vy { x, y }           => write!(f, "XOR V{x:x}, V{y:x}"),
+            Op::SHR_Vx_Vy { x, y }           => write!(f, "SHR V{x:x}, V{y:x}"),
+            Op::SHL_Vx_Vy { x, y }           => write!(f, "SHL V{x:x}, V{y:x}"),
+            Op::RND_Vx_byte { x, nn }        => write!(f, "RND V{x:x}, {nn:#04x}"),
+            Op::JP_addr { nnn }              => write!(f, "JP {nnn:#05x}"),
+            Op::JP_V0_addr { nnn }           => write!(f, "JP V0, {nnn:#05x}"),
+            Op::CALL_addr { nnn }            => write!(f, "CALL {nnn:#05x}"),
             Op::RET {}                       => write!(f, "RET"),
-            Op::SE_Vx_byte { x, nn }         => write!(f, "SE V{:x}, {:#04x}", x, nn),
-            Op::SE_Vx_Vy { x, y }            => write!(f, "SE V{:x}, V{:x}", x, y),
-            Op::SNE_Vx_byte { x, nn }        => write!(f, "SNE V{:x}, {:#04x}", x, nn),
-            Op::SNE_Vx_Vy { x, y }           => write!(f, "SNE V{:x}, V{:x}", x, y),
-            Op::LD_DT_Vx { x }               => write!(f, "LD DT, V{:x}", x),
-            Op::LD_Vx_DT { x }               => write!(f, "LD V{:x}, DT", x),
-            Op::LD_ST_Vx { x }               => write!(f, "LD ST, V{:x}", x),
-            Op::LD_Vx_K { x }                => write!(f, "LD V{:x}, K", x),
-            Op::SKP_Vx { x }                 => write!(f, "SKP V{:x}", x),
-            Op::SKNP_Vx { x }                => write!(f, "SKNP V{:x}", x),
-            Op::LD_I_addr { nnn }            => write!(f, "LD I, {:#05x}", nnn),
-            Op::ADD_I_Vx { x }               => write!(f, "ADD I, V{:x}", x),
-            Op::DRW_Vx_Vy_nibble { x, y, n } => write!(f, "DRW V{:x}, V{:x}, {:x}", x, y, n),
+            Op::SE_Vx_byte { x, nn }         => write!(f, "SE V{x:x}, {nn:#04x}"),
+            Op::SE_Vx_Vy { x, y }            => write!(f, "SE V{x:x}, V{y:x}"),
+            Op::SNE_Vx_byte { x, nn }        => write!(f, "SNE V{x:x}, {nn:#04x}"),
+            Op::SNE_Vx_Vy { x, y }           => write!(f, "SNE V{x:x}, V{y:x}"),
+            Op::LD_DT_Vx { x }               => write!(f, "LD DT, V{x:x}"),
+            Op::LD_Vx_DT { x }               => write!(f, "LD V{x:x}, DT"),
+            Op::LD_ST_Vx { x }               => write!(f, "LD ST, V{x:x}"),
+            Op::LD_Vx_K { x }                => write!(f, "LD V{x:x}, K"),
+            Op::SKP_Vx { x }                 => write!(f, "SKP V{x:x}"),
+            Op::SKNP_Vx { x }                => write!(f, "SKNP V{x:x}"),
+            Op::LD_I_addr { nnn }            => write!(f, "LD I, {nnn:#05x}"),
+            Op::ADD_I_Vx { x }               => write!(f, "ADD I, V{x:x}"),
+            Op::DRW_Vx_Vy_nibble { x, y, n } => write!(f, "DRW V{x:x}, V{y:x}, {n}"),
             Op::CLS {}                       => write!(f, "CLS"),
-            Op::LD_F_Vx { x }                => write!(f, "LD F, V{:x}", x),
-            Op::LD_B_Vx { x }                => write!(f, "LD B, V{:x}", x),
-            Op::LD_I_Vx { x }                => write!(f, "LD [I], V{:x}", x),
-            Op::LD_Vx_I { x }                => write!(f, "LD V{:x}, [I]", x),
+            Op::LD_F_Vx { x }                => write!(f, "LD F, V{x:x}"),
+            Op::LD_B_Vx { x }                => write!(f, "LD B, V{x:x}"),
+            Op::LD_I_Vx { x }                => write!(f, "LD [I], V{x:x}"),
+            Op::LD_Vx_I { x }                => write!(f, "LD V{x:x}, [I]"),
+            Op::SCD_nibble { n }             => write!(f, "SCD {n}"),
+            Op::SCU_nibble { n }             => write!(f, "SCU {n}"),
+            Op::SCR {}                       => write!(f, "SCR"),
+            Op::SCL {}                       => write!(f, "SCL"),
+            Op::EXIT {}                      => write!(f, "EXIT"),
+            Op::LOW {}                       => write!(f, "LOW"),
+            Op::HIGH {}                      => write!(f, "HIGH"),
+            Op::LD_HF_Vx { x }               => write!(f, "LD HF, V{x:x}"),
+            Op::LD_R_Vx { x }                => write!(f, "LD R, V{x:x}"),
+            Op::LD_Vx_R { x }                => write!(f, "LD V{x:x}, R"),
+            Op::LD_I_long { nnnn }           => write!(f, "LD I, LONG {nnnn:#06x}"),
+            Op::SAVE_Vx_Vy { x, y }          => write!(f, "SAVE V{x:x}, V{y:x}"),
+            Op::LOAD_Vx_Vy { x, y }          => write!(f, "LOAD V{x:x}, V{y:x}"),
+            Op::PLANE_n { n }                => write!(f, "PLANE {n}"),
+            Op::AUDIO {}                     => write!(f, "AUDIO"),
+            Op::PITCH_Vx { x }               => write!(f, "PITCH V{x:x}"),
             Op::UNKNOWN {}                   => write!(f, "UNKNOWN"),
         }
     }
@@ -0,0 +1,136 @@
+use std::fs;
+use std::io;
+use std::str::FromStr;
+
+// Behaviours that differ between CHIP-8 interpreters. Each flag is `true`
+// when the interpreter behaves as described.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[allow(clippy::struct_excessive_bools)]
+pub struct Quirks {
+    // 8xy6/8xyE shift Vx in place instead of shifting Vy into Vx.
+    pub shift: bool,
+    // Fx55/Fx65 leave I pointing past the last register stored or loaded.
+    pub load_store: bool,
+    // Bxnn jumps to xnn + Vx instead of nnn + V0.
+    pub jump: bool,
+    // 8xy1/8xy2/8xy3 reset VF to 0.
+    pub vf_reset: bool,
+    // Sprites are clipped at the screen edges instead of wrapping around.
+    pub clipping: bool,
+    // Dxyn waits for the vertical blank, limiting drawing to once per frame.
+    pub display_wait: bool,
+}
+
+impl Default for Quirks {
+    fn default() -> Self {
+        Self::vip()
+    }
+}
+
+impl Quirks {
+    pub const PRESETS: [&'static str; 4] = ["vip", "chip48", "schip", "xochip"];
+
+    // The original COSMAC VIP interpreter.
+    #[must_use]
+    pub fn vip() -> Self {
+        Self {
+            shift: false,
+            load_store: true,
+            jump: false,
+            vf_reset: true,
+            clipping: true,
+            display_wait: true,
+        }
+    }
+
+    // CHIP-48 on the HP-48 calculators.
+    #[must_use]
+    pub fn chip48() -> Self {
+        Self {
+            shift: true,
+            load_store: false,
+            jump: true,
+            vf_reset: false,
+            clipping: true,
+            display_wait: false,
+        }
+    }
+
+    // SUPER-CHIP 1.1.
+    #[must_use]
+    pub fn schip() -> Self {
+        Self::chip48()
+    }
+
+    // XO-CHIP as implemented by Octo.
+    #[must_use]
+    pub fn xochip() -> Self {
+        Self {
+            shift: false,
+            load_store: true,
+            jump: false,
+            vf_reset: false,
+            clipping: false,
+            display_wait: false,
+        }
+    }
+
+    // Applies `<quirk> = <on|off>` lines on top of the VIP preset. A
+    // `preset = <name>` line replaces every flag at once. `#` starts a comment.
+    pub fn parse(text: &str) -> Result<Self, String> {
+        let mut quirks = Self::default();
+
+        for (n, line) in text.lines().enumerate() {
+            let line = line.split('#').next().unwrap_or_default().trim();
+            if line.is_empty() {
+                continue;
+            }
+
+            let (name, value) = line.split_once('=')
+                .map(|(name, value)| (name.trim(), value.trim()))
+                .ok_or_else(|| format!("line {}: expected `<quirk> = <value>`", n + 1))?;
+
+            if name == "preset" {
+                quirks = value.parse().map_err(|e| format!("line {}: {e}", n + 1))?;
+                continue;
+            }
+
+            let value = match value {
+                "on" | "true" | "1" => true,
+                "off" | "false" | "0" => false,
+                _ => return Err(format!("line {}: expected on|off, got {value}", n + 1)),
+            };
+
+            match name {
+                "shift" => quirks.shift = value,
+                "load_store" => quirks.load_store = value,
+                "jump" => quirks.jump = value,
+                "vf_reset" => quirks.vf_reset = value,
+                "clipping" => quirks.clipping = value,
+                "display_wait" => quirks.display_wait = value,
+                _ => return Err(format!("line {}: unknown quirk {name}", n + 1)),
+            }
+        }
+
+        Ok(quirks)
+    }
+
+    pub fn load(path: &str) -> io::Result<Self> {
+        Self::parse(&fs::read_to_string(path)?)
+            .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, format!("{path}: {e}")))
+    }
+}
+
+impl FromStr for Quirks {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "vip" | "chip8" => Ok(Self::vip()),
+            "chip48" => Ok(Self::chip48()),
+            "schip" | "schip11" => Ok(Self::schip()),
+            "xochip" => Ok(Self::xochip()),
+            _ => Err(format!("unknown quirks preset {s}, expected {}", Self::PRESETS.join("|"))),
+        }
+    }
+}
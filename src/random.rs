@@ -0,0 +1,54 @@
+use std::collections::hash_map::RandomState;
+use std::hash::{BuildHasher, Hasher};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+// xorshift64* generator for `RND`. Seeded explicitly so runs can be replayed
+// bit for bit; its whole state is one word and goes into save states.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Xorshift {
+    state: u64,
+}
+
+impl Xorshift {
+    // Any seed works, 0 included; it is scrambled with splitmix64 first.
+    #[must_use]
+    pub fn new(seed: u64) -> Self {
+        let mut z = seed.wrapping_add(0x9e37_79b9_7f4a_7c15);
+        z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+        z ^= z >> 31;
+
+        Self::from_state(z)
+    }
+
+    // Restores a generator from `state()`; a zero state is bumped to 1.
+    #[must_use]
+    pub fn from_state(state: u64) -> Self {
+        Self { state: state.max(1) }
+    }
+
+    #[must_use]
+    pub fn state(&self) -> u64 {
+        self.state
+    }
+
+    pub fn next_u64(&mut self) -> u64 {
+        self.state ^= self.state >> 12;
+        self.state ^= self.state << 25;
+        self.state ^= self.state >> 27;
+        self.state.wrapping_mul(0x2545_f491_4f6c_dd1d)
+    }
+
+    pub fn next_u8(&mut self) -> u8 {
+        self.next_u64().to_be_bytes()[0]
+    }
+}
+
+// A seed that differs from run to run: the time, hashed with the randomly
+// keyed hasher std gives every `HashMap`.
+#[must_use]
+pub fn random_seed() -> u64 {
+    let mut hasher = RandomState::new().build_hasher();
+    hasher.write_u128(SystemTime::now().duration_since(UNIX_EPOCH).map_or(0, |since| since.as_nanos()));
+    hasher.finish()
+}
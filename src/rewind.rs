@@ -0,0 +1,175 @@
+use std::collections::VecDeque;
+use std::mem;
+
+use crate::{Chip8Error, Cpu, SaveState};
+
+pub const DEFAULT_REWIND_BUDGET: usize = 4 * 1024 * 1024;
+
+// How to get from a snapshot back to the one before it: the small state in
+// full, memory and video as run-length encoded XOR against the newer one.
+#[derive(Debug)]
+struct Delta {
+    // `ram` and `video.ram` are left empty.
+    state: SaveState,
+    ram: Vec<u8>,
+    video: Vec<u8>,
+}
+
+impl Delta {
+    fn new(mut older: SaveState, newer: &SaveState) -> Self {
+        let ram = rle(&xor(&older.ram, &newer.ram));
+        let video = rle(&xor(&older.video.ram, &newer.video.ram));
+        older.ram = Vec::new();
+        older.video.ram = Vec::new();
+
+        Self { state: older, ram, video }
+    }
+
+    fn apply(&self, newer: &SaveState) -> SaveState {
+        let mut older = self.state.clone();
+        older.ram = xor(&unrle(&self.ram), &newer.ram);
+        older.video.ram = xor(&unrle(&self.video), &newer.video.ram);
+        older
+    }
+
+    fn size(&self) -> usize {
+        mem::size_of::<Self>() + self.ram.len() + self.video.len()
+    }
+}
+
+// A ring of per-frame snapshots for rewinding, kept as deltas against the
+// newest one and bounded to `budget` bytes by dropping the oldest.
+#[derive(Debug)]
+pub struct Rewind {
+    latest: Option<SaveState>,
+    deltas: VecDeque<Delta>,
+    size: usize,
+    budget: usize,
+}
+
+impl Default for Rewind {
+    fn default() -> Self {
+        Self::new(DEFAULT_REWIND_BUDGET)
+    }
+}
+
+impl Rewind {
+    #[must_use]
+    pub fn new(budget: usize) -> Self {
+        Self { latest: None, deltas: VecDeque::new(), size: 0, budget }
+    }
+
+    #[must_use]
+    pub fn budget(&self) -> usize {
+        self.budget
+    }
+
+    // Bytes held by the deltas, not counting the newest snapshot.
+    #[must_use]
+    pub fn size(&self) -> usize {
+        self.size
+    }
+
+    // Number of snapshots that can be rewound to.
+    #[must_use]
+    pub fn len(&self) -> usize {
+        self.deltas.len() + usize::from(self.latest.is_some())
+    }
+
+    #[must_use]
+    pub fn is_empty(&self) -> bool {
+        self.latest.is_none()
+    }
+
+    pub fn clear(&mut self) {
+        self.latest = None;
+        self.deltas.clear();
+        self.size = 0;
+    }
+
+    // Records the CPU as it is now, normally at the start of every frame
+    // once input has been applied.
+    pub fn push(&mut self, cpu: &Cpu) {
+        let state = cpu.save_state();
+        if let Some(older) = self.latest.take() {
+            let delta = Delta::new(older, &state);
+            self.size += delta.size();
+            self.deltas.push_back(delta);
+        }
+        self.latest = Some(state);
+
+        while self.size > self.budget {
+            match self.deltas.pop_front() {
+                Some(delta) => self.size -= delta.size(),
+                None => break,
+            }
+        }
+    }
+
+    // Drops the newest snapshot, making the one before it the newest.
+    fn pop(&mut self) -> Option<SaveState> {
+        let latest = self.latest.take()?;
+        if let Some(delta) = self.deltas.pop_back() {
+            self.size -= delta.size();
+            self.latest = Some(delta.apply(&latest));
+        }
+        Some(latest)
+    }
+
+    // Restores the newest snapshot and drops it, so calling this once per
+    // frame walks back one frame at a time. False once there is nothing left.
+    pub fn rewind(&mut self, cpu: &mut Cpu) -> bool {
+        self.pop().is_some_and(|state| cpu.load_state(&state).is_ok())
+    }
+
+    // Undoes exactly one instruction by restoring the newest snapshot before
+    // it and replaying up to it. False if that snapshot has been dropped.
+    pub fn step_back(&mut self, cpu: &mut Cpu) -> Result<bool, Chip8Error> {
+        let Some(target) = cpu.cycles().checked_sub(1) else {
+            return Ok(false);
+        };
+
+        while self.latest.as_ref().is_some_and(|state| state.cycles > target) {
+            self.pop();
+        }
+
+        let Some(state) = &self.latest else {
+            return Ok(false);
+        };
+        if cpu.load_state(state).is_err() {
+            return Ok(false);
+        }
+
+        while cpu.cycles() < target {
+            let (_, _, op) = cpu.current();
+            cpu.step(&op)?;
+        }
+
+        Ok(true)
+    }
+}
+
+// As long as `a`; video memory changes size when switching to hires.
+fn xor(a: &[u8], b: &[u8]) -> Vec<u8> {
+    let mut out = a.to_vec();
+    for (out, b) in out.iter_mut().zip(b) {
+        *out ^= b;
+    }
+    out
+}
+
+// `(count, byte)` pairs, count 1..=255.
+fn rle(data: &[u8]) -> Vec<u8> {
+    let mut out = Vec::new();
+    for chunk in data.chunk_by(|a, b| a == b) {
+        for run in chunk.chunks(255) {
+            out.push(u8::try_from(run.len()).unwrap());
+            out.push(run[0]);
+        }
+    }
+    out
+}
+
+fn unrle(data: &[u8]) -> Vec<u8> {
+    data.chunks(2).flat_map(|pair| std::iter::repeat_n(pair[1], usize::from(pair[0]))).collect()
+}
@@ -0,0 +1,260 @@
+use std::fmt;
+use std::fs;
+use std::io;
+
+use crate::{AudioPattern, KeyWait, KeyWaitMode, Keypad, Quirks, Timers, Video};
+
+pub const STATE_MAGIC: &[u8; 4] = b"C8SS";
+pub const STATE_VERSION: u16 = 1;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum StateError {
+    BadMagic,
+    UnsupportedVersion(u16),
+    RomMismatch { expected: u64, found: u64 },
+    Truncated,
+    Invalid(&'static str),
+}
+
+impl fmt::Display for StateError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            StateError::BadMagic                        => write!(f, "not a save state"),
+            StateError::UnsupportedVersion(version)     => write!(f, "unsupported save state version {version}"),
+            StateError::RomMismatch { expected, found } => write!(f, "save state is for ROM {found:016x}, not {expected:016x}"),
+            StateError::Truncated                       => write!(f, "save state is truncated"),
+            StateError::Invalid(field)                  => write!(f, "save state has an invalid {field}"),
+        }
+    }
+}
+
+impl std::error::Error for StateError {}
+
+// 64-bit FNV-1a, used to tie save states to the ROM they were taken from.
+#[must_use]
+pub fn rom_hash(data: &[u8]) -> u64 {
+    data.iter().fold(0xcbf2_9ce4_8422_2325, |hash, b| (hash ^ u64::from(*b)).wrapping_mul(0x0100_0000_01b3))
+}
+
+// Everything needed to resume a `Cpu` exactly where it was, taken with
+// `Cpu::save_state` and applied with `Cpu::load_state`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SaveState {
+    pub rom_hash: u64,
+    pub quirks: Quirks,
+    pub ram: Vec<u8>,
+    pub video: Video,
+    pub v: [u8; 16],
+    pub i: u16,
+    pub pc: usize,
+    pub stack: [usize; 16],
+    pub sp: usize,
+    pub timers: Timers,
+    pub keypad: Keypad,
+    pub wait_key: KeyWait,
+    pub key_wait_mode: KeyWaitMode,
+    pub vblank_wait: bool,
+    pub rpl: [u8; 16],
+    pub halted: bool,
+    pub audio_pattern: Option<AudioPattern>,
+    pub cycles: u64,
+    // `Xorshift::state` of the `RND` generator.
+    pub rng: u64,
+}
+
+impl SaveState {
+    // Header (magic, version, ROM hash, quirks) followed by the machine state,
+    // little-endian. Fails if the PC or a return address is past 0xFFFF.
+    pub fn encode(&self) -> Result<Vec<u8>, StateError> {
+        let mut out = Vec::with_capacity(self.ram.len() + self.video.ram.len() + 128);
+        out.extend_from_slice(STATE_MAGIC);
+        out.extend_from_slice(&STATE_VERSION.to_le_bytes());
+        out.extend_from_slice(&self.rom_hash.to_le_bytes());
+        out.push(quirk_bits(self.quirks));
+
+        out.extend_from_slice(&self.v);
+        out.extend_from_slice(&self.i.to_le_bytes());
+        out.extend_from_slice(&address(self.pc)?);
+        for addr in self.stack {
+            out.extend_from_slice(&address(addr)?);
+        }
+        out.push(u8::try_from(self.sp).unwrap());
+        out.push(self.timers.delay);
+        out.push(self.timers.sound);
+        out.extend_from_slice(&self.keypad.bits().to_le_bytes());
+        match self.wait_key {
+            KeyWait::Idle               => out.extend_from_slice(&[0, 0, 0]),
+            KeyWait::Press { x }        => out.extend_from_slice(&[1, u8::try_from(x).unwrap(), 0]),
+            KeyWait::Release { x, key } => out.extend_from_slice(&[2, u8::try_from(x).unwrap(), key]),
+        }
+        out.push(u8::from(self.key_wait_mode == KeyWaitMode::Modern));
+        out.push(u8::from(self.vblank_wait));
+        out.extend_from_slice(&self.rpl);
+        out.push(u8::from(self.halted));
+        match &self.audio_pattern {
+            Some(pattern) => {
+                out.push(1);
+                out.extend_from_slice(&pattern.bits);
+                out.push(pattern.pitch);
+            }
+            None => out.push(0),
+        }
+        out.extend_from_slice(&self.cycles.to_le_bytes());
+        out.extend_from_slice(&self.rng.to_le_bytes());
+
+        out.push(u8::from(self.video.hires()));
+        out.push(self.video.planes());
+        out.extend_from_slice(&self.video.ram);
+        out.extend_from_slice(&u32::try_from(self.ram.len()).unwrap().to_le_bytes());
+        out.extend_from_slice(&self.ram);
+
+        Ok(out)
+    }
+
+    pub fn decode(data: &[u8]) -> Result<Self, StateError> {
+        let mut r = Reader { data };
+        if r.bytes(STATE_MAGIC.len())? != STATE_MAGIC {
+            return Err(StateError::BadMagic);
+        }
+        let version = r.u16()?;
+        if version != STATE_VERSION {
+            return Err(StateError::UnsupportedVersion(version));
+        }
+
+        let rom_hash = r.u64()?;
+        let quirks = quirks_from_bits(r.u8()?);
+
+        let v = r.array()?;
+        let i = r.u16()?;
+        let pc = usize::from(r.u16()?);
+        let mut stack = [0; 16];
+        for addr in &mut stack {
+            *addr = usize::from(r.u16()?);
+        }
+        let sp = usize::from(r.u8()?);
+        if sp > stack.len() {
+            return Err(StateError::Invalid("stack pointer"));
+        }
+        let timers = Timers { delay: r.u8()?, sound: r.u8()? };
+        let mut keypad = Keypad::new();
+        let bits = r.u16()?;
+        for key in (0..16).filter(|key| bits & (1 << key) != 0) {
+            keypad.press(key);
+        }
+        let wait_key = match r.array::<3>()? {
+            [0, _, _]   => KeyWait::Idle,
+            [1, x, _]   if x < 16 => KeyWait::Press { x: usize::from(x) },
+            [2, x, key] if x < 16 => KeyWait::Release { x: usize::from(x), key },
+            _           => return Err(StateError::Invalid("key wait")),
+        };
+        let key_wait_mode = if r.u8()? == 0 { KeyWaitMode::Vip } else { KeyWaitMode::Modern };
+        let vblank_wait = r.u8()? != 0;
+        let rpl = r.array()?;
+        let halted = r.u8()? != 0;
+        let audio_pattern = match r.u8()? {
+            0 => None,
+            _ => Some(AudioPattern { bits: r.array()?, pitch: r.u8()? }),
+        };
+        let cycles = r.u64()?;
+        let rng = r.u64()?;
+
+        let mut video = Video::new();
+        video.set_hires(r.u8()? != 0);
+        video.set_planes(r.u8()?);
+        let len = video.ram.len();
+        video.ram.copy_from_slice(r.bytes(len)?);
+        let len = usize::try_from(r.u32()?).map_err(|_| StateError::Invalid("memory size"))?;
+        let ram = r.bytes(len)?.to_vec();
+
+        if !r.data.is_empty() {
+            return Err(StateError::Invalid("length"));
+        }
+
+        Ok(Self {
+            rom_hash,
+            quirks,
+            ram,
+            video,
+            v,
+            i,
+            pc,
+            stack,
+            sp,
+            timers,
+            keypad,
+            wait_key,
+            key_wait_mode,
+            vblank_wait,
+            rpl,
+            halted,
+            audio_pattern,
+            cycles,
+            rng,
+        })
+    }
+
+    pub fn save(&self, path: &str) -> io::Result<()> {
+        let data = self.encode().map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+        fs::write(path, data)
+    }
+
+    pub fn load(path: &str) -> io::Result<Self> {
+        Self::decode(&fs::read(path)?).map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))
+    }
+}
+
+fn address(addr: usize) -> Result<[u8; 2], StateError> {
+    u16::try_from(addr).map(u16::to_le_bytes).map_err(|_| StateError::Invalid("address"))
+}
+
+pub(crate) fn quirk_bits(quirks: Quirks) -> u8 {
+    let quirks = [quirks.shift, quirks.load_store, quirks.jump, quirks.vf_reset, quirks.clipping, quirks.display_wait];
+    (0..).zip(quirks).fold(0, |bits, (n, on)| bits | u8::from(on) << n)
+}
+
+pub(crate) fn quirks_from_bits(bits: u8) -> Quirks {
+    let on = |n: u8| bits & (1 << n) != 0;
+    Quirks {
+        shift: on(0),
+        load_store: on(1),
+        jump: on(2),
+        vf_reset: on(3),
+        clipping: on(4),
+        display_wait: on(5),
+    }
+}
+
+struct Reader<'a> {
+    data: &'a [u8],
+}
+
+impl<'a> Reader<'a> {
+    fn bytes(&mut self, len: usize) -> Result<&'a [u8], StateError> {
+        if self.data.len() < len {
+            return Err(StateError::Truncated);
+        }
+        let (bytes, rest) = self.data.split_at(len);
+        self.data = rest;
+        Ok(bytes)
+    }
+
+    fn array<const N: usize>(&mut self) -> Result<[u8; N], StateError> {
+        Ok(self.bytes(N)?.try_into().unwrap())
+    }
+
+    fn u8(&mut self) -> Result<u8, StateError> {
+        Ok(self.bytes(1)?[0])
+    }
+
+    fn u16(&mut self) -> Result<u16, StateError> {
+        Ok(u16::from_le_bytes(self.array()?))
+    }
+
+    fn u32(&mut self) -> Result<u32, StateError> {
+        Ok(u32::from_le_bytes(self.array()?))
+    }
+
+    fn u64(&mut self) -> Result<u64, StateError> {
+        Ok(u64::from_le_bytes(self.array()?))
+    }
+}
@@ -0,0 +1,72 @@
+use crate::{Chip8Error, Cpu};
+use crate::timer::TIMER_HZ;
+
+use log::debug;
+
+pub const DEFAULT_CLOCK_HZ: u32 = 700;
+pub const MIN_CLOCK_HZ: u32 = TIMER_HZ;
+pub const MAX_CLOCK_HZ: u32 = 100_000;
+pub const CLOCK_HZ_STEP: u32 = 100;
+
+// Runs the CPU at `clock_hz` instructions per second, split into 60 Hz frames.
+// Rates that are not a multiple of 60 carry the remainder into the next frame.
+#[derive(Debug, Clone, Copy)]
+pub struct Scheduler {
+    clock_hz: u32,
+    carry: u32,
+}
+
+impl Default for Scheduler {
+    fn default() -> Self {
+        Self::new(DEFAULT_CLOCK_HZ)
+    }
+}
+
+impl Scheduler {
+    #[must_use]
+    pub fn new(clock_hz: u32) -> Self {
+        Self {
+            clock_hz: clock_hz.clamp(MIN_CLOCK_HZ, MAX_CLOCK_HZ),
+            carry: 0,
+        }
+    }
+
+    #[must_use]
+    pub fn clock_hz(&self) -> u32 {
+        self.clock_hz
+    }
+
+    pub fn set_clock_hz(&mut self, clock_hz: u32) {
+        self.clock_hz = clock_hz.clamp(MIN_CLOCK_HZ, MAX_CLOCK_HZ);
+        self.carry = 0;
+    }
+
+    pub fn faster(&mut self) {
+        self.set_clock_hz(self.clock_hz.saturating_add(CLOCK_HZ_STEP));
+    }
+
+    pub fn slower(&mut self) {
+        self.set_clock_hz(self.clock_hz.saturating_sub(CLOCK_HZ_STEP));
+    }
+
+    pub fn instructions_per_frame(&mut self) -> u32 {
+        let total = self.clock_hz + self.carry;
+        self.carry = total % TIMER_HZ;
+        total / TIMER_HZ
+    }
+
+    pub fn frame(&mut self, cpu: &mut Cpu) -> Result<(), Chip8Error> {
+        for _ in 0..self.instructions_per_frame() {
+            if cpu.waiting() || cpu.halted() {
+                break;
+            }
+
+            let (pc, word, op) = cpu.current();
+            debug!("{pc:#06x} {word:#06x} {op}");
+            cpu.step(&op)?;
+        }
+
+        cpu.tick_timers();
+        Ok(())
+    }
+}
@@ -0,0 +1,384 @@
+use bracket_lib::prelude::*;
+use rschip8::prelude::*;
+
+use log::{error, info, warn};
+
+const PANEL_X: i32 = HIRES_WIDTH + 1;
+const PANEL_WIDTH: i32 = 40;
+
+pub struct Terminal {
+    pub cpu: Cpu,
+    pub scheduler: Scheduler,
+    pub beeper: Beeper<Box<dyn AudioSink>>,
+    pub keymap: KeyMap,
+    pub crash: Option<Chip8Error>,
+    pub debugger: Debugger,
+    pub show_debugger: bool,
+    pub gdb: Option<GdbStub>,
+    pub rom_path: String,
+    pub rewind: Rewind,
+    pub playback: Option<Movie>,
+    // The movie being recorded and where it is saved on quit.
+    pub recording: Option<(Movie, String)>,
+    frames: u64,
+}
+
+#[allow(clippy::enum_glob_use)]
+fn host_char(key: VirtualKeyCode) -> Option<char> {
+    use VirtualKeyCode::*;
+
+    let c = match key {
+        Key0 => '0', Key1 => '1', Key2 => '2', Key3 => '3', Key4 => '4',
+        Key5 => '5', Key6 => '6', Key7 => '7', Key8 => '8', Key9 => '9',
+        A => 'a', B => 'b', C => 'c', D => 'd', E => 'e', F => 'f', G => 'g',
+        H => 'h', I => 'i', J => 'j', K => 'k', L => 'l', M => 'm', N => 'n',
+        O => 'o', P => 'p', Q => 'q', R => 'r', S => 's', T => 't', U => 'u',
+        V => 'v', W => 'w', X => 'x', Y => 'y', Z => 'z',
+        Comma => ',', Period => '.', Slash => '/', Semicolon => ';',
+        Apostrophe => '\'', LBracket => '[', RBracket => ']', Minus => '-',
+        Equals => '=', Space => ' ',
+        _ => return None,
+    };
+
+    Some(c)
+}
+
+impl Terminal {
+    pub fn new(cpu: Cpu, scheduler: Scheduler, audio: Box<dyn AudioSink>, keymap: KeyMap, debugger: Debugger, gdb: Option<GdbStub>, rom_path: &str) -> Self {
+        Self {
+            cpu,
+            scheduler,
+            beeper: Beeper::new(audio),
+            keymap,
+            crash: None,
+            debugger,
+            show_debugger: false,
+            gdb,
+            rom_path: rom_path.to_string(),
+            rewind: Rewind::default(),
+            playback: None,
+            recording: None,
+            frames: 0,
+        }
+    }
+
+    fn movie_active(&self) -> bool {
+        self.playback.is_some() || self.recording.is_some()
+    }
+
+    // Feeds the keypad from the movie being played, or the host keyboard
+    // once it ends, and records the result.
+    fn update_input(&mut self) {
+        match &self.playback {
+            Some(movie) if movie.play(self.frames, &mut self.cpu) => {}
+            Some(movie) => {
+                match movie.verify(&self.cpu.video) {
+                    Some(true) => info!("movie ended, screen matches the recording"),
+                    Some(false) => warn!("movie ended, screen differs from the recording"),
+                    None => info!("movie ended"),
+                }
+                self.playback = None;
+                self.update_keypad();
+            }
+            None => self.update_keypad(),
+        }
+
+        if let Some((movie, _)) = &mut self.recording {
+            movie.record(self.cpu.keypad());
+        }
+        self.frames += 1;
+    }
+
+    fn quit(&mut self, ctx: &mut BTerm) {
+        if let Some((mut movie, path)) = self.recording.take() {
+            movie.finish(&self.cpu.video);
+            match movie.save(&path) {
+                Ok(()) => info!("saved movie of {} frames to {path}", movie.frames.len()),
+                Err(e) => error!("saving movie to {path} failed: {e}"),
+            }
+        }
+        ctx.quitting = true;
+    }
+
+    fn state_path(&self, slot: usize) -> String {
+        std::path::Path::new(&self.rom_path).with_extension(format!("state{slot}")).to_string_lossy().into_owned()
+    }
+
+    // F1-F4 load a save state slot, Shift+F1-F4 save to it.
+    fn handle_state_key(&mut self, ctx: &BTerm) {
+        let slot = match ctx.key {
+            Some(VirtualKeyCode::F1) => 1,
+            Some(VirtualKeyCode::F2) => 2,
+            Some(VirtualKeyCode::F3) => 3,
+            Some(VirtualKeyCode::F4) => 4,
+            _ => return,
+        };
+
+        let path = self.state_path(slot);
+        if !ctx.shift && self.movie_active() {
+            warn!("states cannot be loaded while a movie records or plays");
+            return;
+        }
+        if ctx.shift {
+            match self.cpu.save_state().save(&path) {
+                Ok(()) => info!("saved state {slot} to {path}"),
+                Err(e) => warn!("saving state {slot} failed: {e}"),
+            }
+            return;
+        }
+
+        let result = SaveState::load(&path)
+            .and_then(|state| self.cpu.load_state(&state).map_err(|e| std::io::Error::new(std::io::ErrorKind::InvalidData, e)));
+        match result {
+            Ok(()) => info!("loaded state {slot} from {path}"),
+            Err(e) => warn!("loading state {slot} failed: {e}"),
+        }
+    }
+
+    fn update_keypad(&mut self) {
+        let mut keypad = Keypad::new();
+        for key in INPUT.lock().key_pressed_set() {
+            if let Some(key) = host_char(*key).and_then(|c| self.keymap.get(c)) {
+                keypad.press(key);
+            }
+        }
+
+        for key in 0..16 {
+            match (keypad.is_pressed(key), self.cpu.keypad().is_pressed(key)) {
+                (true, false) => self.cpu.press(key),
+                (false, true) => self.cpu.release(key),
+                _ => {}
+            }
+        }
+    }
+
+    // The console is sized for SUPER-CHIP hires; lores pixels cover 2x2 cells.
+    fn render(&self, ctx: &mut BTerm) {
+        let video = &self.cpu.video;
+        let scale = HIRES_WIDTH / video.width();
+        for y in 0..HIRES_HEIGHT {
+            for x in 0..HIRES_WIDTH {
+                let idx = video.map_idx(x / scale, y / scale);
+                match video.ram[idx] {
+                    0 => {
+                        ctx.set(x, y, YELLOW, BLACK,
+                            to_cp437('.')
+                        );
+                    }
+                    1 => {
+                        ctx.set(x, y, GREEN, BLACK,
+                            to_cp437('#')
+                        );
+                    }
+                    2 => {
+                        ctx.set(x, y, CYAN, BLACK,
+                            to_cp437('#')
+                        );
+                    }
+                    _ => {
+                        ctx.set(x, y, WHITE, BLACK,
+                            to_cp437('#')
+                        );
+                    }
+                }
+            }
+        }
+    }
+
+    fn render_debugger(&self, ctx: &mut BTerm) {
+        let state = match (self.debugger.paused(), self.debugger.last_break()) {
+            (false, _) => "RUNNING".to_string(),
+            (true, Some(Break::Breakpoint(addr))) => format!("PAUSED at breakpoint {addr:#06x}"),
+            (true, Some(Break::Watchpoint { index, pc })) => format!("PAUSED on watch {index} at {pc:#06x}"),
+            (true, _) => "PAUSED".to_string(),
+        };
+        ctx.print_color(PANEL_X, 0, YELLOW, BLACK, state);
+
+        let cpu = &self.cpu;
+        ctx.print(PANEL_X, 2, format!("PC {:#06x}  I {:#06x}  SP {:x}", cpu.pc(), cpu.i(), cpu.sp()));
+        ctx.print(PANEL_X, 3, format!("DT {:02x}  ST {:02x}", cpu.timers().delay, cpu.timers().sound));
+        let mut y = 4;
+        for (row, regs) in (0..).zip(cpu.v().chunks(4)) {
+            let line = (0..).zip(regs).map(|(n, v)| format!("V{:X} {v:02x}", row * 4 + n)).collect::<Vec<_>>();
+            ctx.print(PANEL_X, y, line.join("  "));
+            y += 1;
+        }
+
+        let stack = self.cpu.stack().iter().map(|addr| format!("{addr:03x}")).collect::<Vec<_>>();
+        ctx.print(PANEL_X, y, format!("STACK {}", stack.join(" ")));
+        y += 2;
+
+        for (addr, word, op) in Debugger::disassembly(&self.cpu, 8, 16) {
+            let marker = if addr == self.cpu.pc() { '>' } else { ' ' };
+            let bp = if self.debugger.breakpoints().contains(&addr) { '*' } else { ' ' };
+            let color = if addr == self.cpu.pc() { WHITE } else { GRAY };
+            ctx.print_color(PANEL_X, y, color, BLACK, format!("{bp}{marker}{addr:04x} {word:04x} {op}"));
+            y += 1;
+        }
+
+        y += 1;
+        for (n, watch) in self.debugger.watches().iter().enumerate() {
+            ctx.print(PANEL_X, y, format!("WATCH {n} {watch}"));
+            y += 1;
+        }
+
+        y += 1;
+        for help in ["F5 pause/resume  F9 breakpoint", "F10 step over  F11 step  F8 step back", "Shift+F11 run to return  Tab hide"] {
+            ctx.print_color(PANEL_X, y, GRAY, BLACK, help);
+            y += 1;
+        }
+    }
+
+    fn handle_debugger_key(&mut self, ctx: &BTerm) -> Result<(), Chip8Error> {
+        match ctx.key {
+            Some(VirtualKeyCode::Tab) => {
+                self.show_debugger = !self.show_debugger;
+            }
+            // A movie holds whole frames only, so no pausing or stepping.
+            Some(VirtualKeyCode::F5 | VirtualKeyCode::F8 | VirtualKeyCode::F9 | VirtualKeyCode::F10 | VirtualKeyCode::F11)
+                if self.movie_active() =>
+            {
+                warn!("the debugger cannot pause or step while a movie records or plays");
+            }
+            Some(VirtualKeyCode::F5) if self.debugger.paused() => {
+                self.debugger.resume(&self.cpu);
+            }
+            Some(VirtualKeyCode::F5) => {
+                self.debugger.pause();
+            }
+            Some(VirtualKeyCode::F9) => {
+                self.debugger.toggle_breakpoint(self.cpu.pc());
+            }
+            Some(VirtualKeyCode::F10) => {
+                let stepped = self.debugger.step_over(&mut self.cpu)?;
+                self.report_step(stepped);
+            }
+            Some(VirtualKeyCode::F11) if ctx.shift => {
+                self.debugger.run_to_return(&self.cpu);
+            }
+            Some(VirtualKeyCode::F11) => {
+                let stepped = self.debugger.step(&mut self.cpu)?;
+                self.report_step(stepped);
+            }
+            Some(VirtualKeyCode::F8) => {
+                self.debugger.pause();
+                if !self.rewind.step_back(&mut self.cpu)? {
+                    warn!("no history to step back into");
+                }
+            }
+            _ => {}
+        }
+
+        Ok(())
+    }
+
+    fn report_step(&self, stepped: bool) {
+        match (stepped, self.cpu.halted()) {
+            (true, _)      => {}
+            (false, true)  => warn!("the program has exited"),
+            (false, false) => warn!("waiting for a key at {:#06x}, press one to step on", self.cpu.pc()),
+        }
+    }
+
+    fn render_crash(&self, ctx: &mut BTerm, e: &Chip8Error) {
+        ctx.print_color(1, 1, RED, BLACK, format!("CRASH: {e}"));
+        for (y, line) in (3..).zip(self.cpu.to_string().lines()) {
+            ctx.print(1, y, line);
+        }
+        ctx.print(1, 7, "Escape to quit");
+    }
+}
+
+impl GameState for Terminal {
+    fn tick(&mut self, ctx: &mut BTerm) {
+        ctx.cls();
+
+        if let Some(gdb) = &mut self.gdb {
+            if let Err(e) = gdb.poll(&mut self.cpu, &mut self.debugger) {
+                warn!("gdb server stopped: {e}");
+                self.gdb = None;
+            }
+        }
+
+        if let Some(e) = &self.crash {
+            self.render_crash(ctx, e);
+            if ctx.key == Some(VirtualKeyCode::Escape) {
+                self.quit(ctx);
+            }
+            return;
+        }
+
+        self.render(ctx);
+        if self.show_debugger {
+            self.render_debugger(ctx);
+        }
+
+        // Holding Backspace rewinds a frame per tick instead of running one.
+        // Movies replay frame by frame from the start, so there is no
+        // rewinding while one records or plays.
+        let result = if INPUT.lock().key_pressed_set().contains(&VirtualKeyCode::Back) && !self.movie_active() {
+            self.rewind.rewind(&mut self.cpu);
+            Ok(None)
+        } else {
+            self.handle_state_key(ctx);
+            self.handle_debugger_key(ctx).and_then(|()| {
+                // Keys still reach a paused CPU, so `LD Vx, K` can be
+                // stepped through.
+                if self.debugger.paused() {
+                    self.update_keypad();
+                } else {
+                    self.update_input();
+                    self.rewind.push(&self.cpu);
+                }
+                self.debugger.frame(&mut self.scheduler, &mut self.cpu)
+            })
+        };
+        match result {
+            Ok(Some(reason)) => {
+                info!("break: {reason:?} at {:#06x}", self.cpu.pc());
+                self.show_debugger = true;
+            }
+            Ok(None) => {}
+            Err(e) => {
+                error!("{e}\n{}", self.cpu);
+                if let Some(gdb) = &mut self.gdb {
+                    gdb.crashed(&e, &mut self.debugger);
+                }
+                self.crash = Some(e);
+            }
+        }
+
+        if let Err(e) = self.beeper.frame(self.cpu.timers(), self.cpu.audio_pattern()) {
+            warn!("audio disabled: {e}");
+            self.beeper = Beeper::new(Box::new(NullSink));
+        }
+
+        match ctx.key {
+            Some(VirtualKeyCode::Escape) => {
+                self.quit(ctx);
+            }
+            Some(VirtualKeyCode::PageUp | VirtualKeyCode::PageDown) if self.movie_active() => {
+                warn!("the clock is fixed while a movie records or plays");
+            }
+            Some(VirtualKeyCode::PageUp) => {
+                self.scheduler.faster();
+                info!("clock {} Hz", self.scheduler.clock_hz());
+            }
+            Some(VirtualKeyCode::PageDown) => {
+                self.scheduler.slower();
+                info!("clock {} Hz", self.scheduler.clock_hz());
+            }
+            _ => {}
+        }
+    }
+}
+
+pub fn run(terminal: Terminal) -> BError {
+    let context = BTermBuilder::simple(HIRES_WIDTH + PANEL_WIDTH, HIRES_HEIGHT)
+        .unwrap()
+        .with_title(&terminal.rom_path)
+        .with_fps_cap(60.0)
+        .build()?;
+
+    main_loop(context, terminal)
+}
@@ -0,0 +1,24 @@
+pub const TIMER_HZ: u32 = 60;
+
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct Timers {
+    pub delay: u8,
+    pub sound: u8,
+}
+
+impl Timers {
+    #[must_use]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn tick(&mut self) {
+        self.delay = self.delay.saturating_sub(1);
+        self.sound = self.sound.saturating_sub(1);
+    }
+
+    #[must_use]
+    pub fn sounding(&self) -> bool {
+        self.sound > 0
+    }
+}
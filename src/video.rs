@@ -1,74 +1,189 @@
 use crate::prelude::*;
 
-const NUM_PIXELS: usize = (SCREEN_WIDTH * SCREEN_HEIGHT) as usize;
+pub const PLANES: u8 = 2;
+const PLANE_MASK: u8 = (1 << PLANES) - 1;
 
-#[derive(Debug)]
+// Each pixel holds one bit per XO-CHIP plane; plain CHIP-8 only uses plane 1.
+#[derive(Debug, Clone, PartialEq, Eq)]
 pub struct Video {
     pub ram: Vec<u8>,
+    width: i32,
+    height: i32,
+    planes: u8,
 }
 
-pub fn map_idx(x: i32, y: i32) -> usize {
-    ((y * SCREEN_WIDTH) + x) as usize
-}
-
-pub fn in_bounds(x: i32, y: i32) -> bool {
-    x >= 0 && x < SCREEN_WIDTH && y >= 0 && y < SCREEN_HEIGHT
+impl Default for Video {
+    fn default() -> Self {
+        Self::new()
+    }
 }
 
 impl Video {
+    #[must_use]
     pub fn new() -> Self {
+        Self::with_size(SCREEN_WIDTH, SCREEN_HEIGHT)
+    }
+
+    #[must_use]
+    #[allow(clippy::cast_sign_loss)]
+    fn with_size(width: i32, height: i32) -> Self {
         Self {
-            ram: vec![0u8; NUM_PIXELS],
+            ram: vec![0u8; (width * height) as usize],
+            width,
+            height,
+            planes: 1,
+        }
+    }
+
+    #[must_use]
+    pub fn width(&self) -> i32 {
+        self.width
+    }
+
+    #[must_use]
+    pub fn height(&self) -> i32 {
+        self.height
+    }
+
+    #[must_use]
+    pub fn hires(&self) -> bool {
+        self.width == HIRES_WIDTH
+    }
+
+    // Switching resolution clears every plane, like SUPER-CHIP does.
+    pub fn set_hires(&mut self, hires: bool) {
+        let planes = self.planes;
+        *self = if hires {
+            Self::with_size(HIRES_WIDTH, HIRES_HEIGHT)
+        } else {
+            Self::new()
+        };
+        self.planes = planes;
+    }
+
+    #[must_use]
+    #[allow(clippy::cast_sign_loss)]
+    pub fn map_idx(&self, x: i32, y: i32) -> usize {
+        ((y * self.width) + x) as usize
+    }
+
+    #[must_use]
+    pub fn in_bounds(&self, x: i32, y: i32) -> bool {
+        (0..self.width).contains(&x) && (0..self.height).contains(&y)
+    }
+
+    // Bitmask of the XO-CHIP planes that drawing, clearing and scrolling act on.
+    #[must_use]
+    pub fn planes(&self) -> u8 {
+        self.planes
+    }
+
+    pub fn set_planes(&mut self, planes: u8) {
+        self.planes = planes & PLANE_MASK;
+    }
+
+    #[must_use]
+    pub fn plane_count(&self) -> usize {
+        self.planes.count_ones() as usize
+    }
+
+    // 8 pixel wide sprite, one byte per row. With several planes selected the
+    // sprite holds one image per plane, back to back.
+    pub fn draw(&mut self, sprite: &[u8], x: i32, y: i32, clip: bool) -> u8 {
+        self.draw_planes(sprite, x, y, clip, |data| {
+            data.iter().map(|byte| u16::from(*byte) << 8).collect()
+        })
+    }
+
+    // SUPER-CHIP 16x16 sprite, two bytes per row.
+    pub fn draw_wide(&mut self, sprite: &[u8], x: i32, y: i32, clip: bool) -> u8 {
+        self.draw_planes(sprite, x, y, clip, |data| {
+            data.chunks(2).map(|row| u16::from_be_bytes([row[0], row.get(1).copied().unwrap_or(0)])).collect()
+        })
+    }
+
+    fn draw_planes(&mut self, sprite: &[u8], x: i32, y: i32, clip: bool, rows: impl Fn(&[u8]) -> Vec<u16>) -> u8 {
+        let count = self.plane_count().max(1);
+        let len = sprite.len() / count;
+        let selected = self.planes;
+        let mut result = 0;
+        for (n, plane) in (0..PLANES).map(|p| 1 << p).filter(|p| selected & p != 0).enumerate() {
+            result |= self.blit(&rows(&sprite[n * len..(n + 1) * len]), plane, x, y, clip);
         }
+
+        result
     }
 
-    pub fn draw(&mut self, sprite: &[u8], x: i32, y: i32) -> u8 {
-        let mut yy = 0;
+    // The start position always wraps; pixels running off the edge are
+    // either clipped or wrapped around to the other side.
+    fn blit(&mut self, rows: &[u16], plane: u8, x: i32, y: i32, clip: bool) -> u8 {
+        let x = x.rem_euclid(self.width);
+        let y = y.rem_euclid(self.height);
         let mut result = 0;
-        for byte in sprite {
-            for xx in 0..8 {
-                if in_bounds(x + xx, y + yy) {
-                    let idx = map_idx(x + xx, y + yy);
-                    let val = byte >> (7 - xx) & 1;
-                    if self.ram[idx] != 0 && val != 0 {
-                        result = 1;
+        for (yy, row) in (0..).zip(rows) {
+            for xx in 0..16 {
+                if row >> (15 - xx) & 1 == 0 {
+                    continue;
+                }
+
+                let (mut px, mut py) = (x + xx, y + yy);
+                if !self.in_bounds(px, py) {
+                    if clip {
+                        continue;
                     }
+                    px = px.rem_euclid(self.width);
+                    py = py.rem_euclid(self.height);
+                }
 
-                    self.ram[idx] = self.ram[idx] ^ val;
+                let idx = self.map_idx(px, py);
+                if self.ram[idx] & plane != 0 {
+                    result = 1;
                 }
+
+                self.ram[idx] ^= plane;
             }
-            yy += 1;
         }
 
         result
     }
 
     pub fn clear(&mut self) {
-        for y in 0..SCREEN_HEIGHT {
-            for x in 0..SCREEN_WIDTH {
-                let idx = map_idx(x, y);
-                self.ram[idx] = 0;
-            }
+        let planes = self.planes;
+        for pixel in &mut self.ram {
+            *pixel &= !planes;
         }
     }
 
-    pub fn render(&self, ctx: &mut BTerm) {
-        for y in 0..SCREEN_HEIGHT {
-            for x in 0..SCREEN_WIDTH {
-                let idx = map_idx(x, y);
-                match self.ram[idx] {
-                    0 => {
-                        ctx.set(x, y, YELLOW, BLACK,
-                            to_cp437('.')
-                        );
-                    }
-                    _ => {
-                        ctx.set(x, y, GREEN, BLACK,
-                            to_cp437('#')
-                        );
-                    }
-                }
+    pub fn scroll_down(&mut self, n: i32) {
+        self.scroll(0, n);
+    }
+
+    pub fn scroll_up(&mut self, n: i32) {
+        self.scroll(0, -n);
+    }
+
+    pub fn scroll_right(&mut self, n: i32) {
+        self.scroll(n, 0);
+    }
+
+    pub fn scroll_left(&mut self, n: i32) {
+        self.scroll(-n, 0);
+    }
+
+    // Moves the selected planes by (dx, dy), filling the gap with blank pixels.
+    fn scroll(&mut self, dx: i32, dy: i32) {
+        let planes = self.planes;
+        let old = self.ram.clone();
+        for y in 0..self.height {
+            for x in 0..self.width {
+                let idx = self.map_idx(x, y);
+                let moved = if self.in_bounds(x - dx, y - dy) {
+                    old[self.map_idx(x - dx, y - dy)] & planes
+                } else {
+                    0
+                };
+                self.ram[idx] = (old[idx] & !planes) | moved;
             }
         }
     }
-}
\ No newline at end of file
+}
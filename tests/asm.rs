@@ -0,0 +1,77 @@
+use std::fs;
+use std::path::PathBuf;
+
+use rschip8::prelude::*;
+
+// A directory of its own under the system temp dir, emptied first.
+fn scratch(name: &str) -> PathBuf {
+    let dir = std::env::temp_dir().join(format!("rschip8-asm-{name}-{}", std::process::id()));
+    let _ = fs::remove_dir_all(&dir);
+    fs::create_dir_all(&dir).unwrap();
+    dir
+}
+
+#[test]
+fn labels_constants_and_forward_references() {
+    let rom = assemble("
+        SPEED = STEP + 1   ; defined before STEP
+        STEP = 2
+    start:
+        LD V0, SPEED
+        CALL sub
+        JP start
+    sub:
+        ADD V0, -1
+        RET
+        dw sub - start, 0x1234
+        db \"ok\", 0b1010_1010
+    ").unwrap();
+
+    assert_eq!(rom, [
+        0x60, 0x03,
+        0x22, 0x06,
+        0x12, 0x00,
+        0x70, 0xff,
+        0x00, 0xee,
+        0x00, 0x06, 0x12, 0x34,
+        b'o', b'k', 0xaa,
+    ]);
+}
+
+#[test]
+fn include_is_relative_to_the_including_file() {
+    let dir = scratch("include");
+    fs::create_dir_all(dir.join("lib")).unwrap();
+    fs::write(dir.join("main.s"), "include \"lib/font.s\"\nLD I, glyph\n").unwrap();
+    fs::write(dir.join("lib/font.s"), "glyph:\n    db 0xf0\n").unwrap();
+
+    let rom = assemble_file(&dir.join("main.s").to_string_lossy()).unwrap();
+    assert_eq!(rom, [0xf0, 0xa2, 0x00]);
+}
+
+#[test]
+fn errors_point_at_the_source() {
+    let error = assemble("LD V0, 1\n    JP nowhere\n").unwrap_err();
+    assert_eq!((error.line, error.col, error.message.as_str()), (2, 8, "undefined name `nowhere`"));
+
+    let error = assemble("LOOP = 1 + LOOP\ndb LOOP\n").unwrap_err();
+    assert_eq!((error.line, error.col, error.message.as_str()), (1, 12, "`LOOP` is defined in terms of itself"));
+
+    let error = assemble("NOP\n").unwrap_err();
+    assert_eq!((error.line, error.col, error.message.as_str()), (1, 1, "unknown instruction `NOP`"));
+
+    let error = assemble("db 0x7fffffffffffffff + 1\n").unwrap_err();
+    assert_eq!((error.line, error.col, error.message.as_str()), (1, 4, "arithmetic overflow"));
+}
+
+#[test]
+fn include_cycles_are_reported() {
+    let dir = scratch("cycle");
+    fs::write(dir.join("a.s"), "CLS\ninclude \"b.s\"\n").unwrap();
+    fs::write(dir.join("b.s"), "\n  include \"a.s\"\n").unwrap();
+
+    // Reported where the include limit runs out, at an even depth in `a.s`.
+    let error = assemble_file(&dir.join("a.s").to_string_lossy()).unwrap_err();
+    assert!(error.file.ends_with("a.s"), "{}", error.file);
+    assert_eq!((error.line, error.col, error.message.as_str()), (2, 1, "includes nested too deeply"));
+}
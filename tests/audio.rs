@@ -0,0 +1,55 @@
+use std::io::Cursor;
+
+use rschip8::prelude::*;
+
+const FRAMES: usize = 20;
+
+// Sounds the tone for 10 ticks of the sound timer, then idles.
+fn rom() -> Vec<u8> {
+    assemble("
+        LD V0, 10
+        LD ST, V0
+    idle:
+        JP idle
+    ").unwrap()
+}
+
+fn u32_at(wav: &[u8], at: usize) -> u32 {
+    u32::from_le_bytes(wav[at..at + 4].try_into().unwrap())
+}
+
+#[test]
+fn sound_timer_drives_a_wav_file() {
+    let mut cpu = Cpu::new(&rom()).unwrap();
+    let mut scheduler = Scheduler::default();
+    let mut beeper = Beeper::new(WavSink::new(Cursor::new(Vec::new())).unwrap());
+    for _ in 0..FRAMES {
+        scheduler.frame(&mut cpu).unwrap();
+        beeper.frame(cpu.timers(), cpu.audio_pattern()).unwrap();
+    }
+    let wav = beeper.into_sink().finish().unwrap().into_inner();
+
+    let data_len = FRAMES * SAMPLES_PER_FRAME * 2;
+    assert_eq!(&wav[0..4], b"RIFF");
+    assert_eq!(u32_at(&wav, 4), u32::try_from(36 + data_len).unwrap());
+    assert_eq!(&wav[8..16], b"WAVEfmt ");
+    assert_eq!(u32_at(&wav, 24), SAMPLE_RATE);
+    assert_eq!(&wav[36..40], b"data");
+    assert_eq!(u32_at(&wav, 40), u32::try_from(data_len).unwrap());
+    assert_eq!(wav.len(), 44 + data_len);
+
+    let samples = wav[44..].chunks(2).map(|s| i16::from_le_bytes([s[0], s[1]])).collect::<Vec<_>>();
+    let frames = samples.chunks(SAMPLES_PER_FRAME).collect::<Vec<_>>();
+
+    // The timer is set in the first frame and ticks once per frame after
+    // that, so the tone plays while it counts down from 9 to 1.
+    for frame in &frames[..9] {
+        assert!(frame.iter().all(|s| s.abs() == BEEP_VOLUME), "tone expected");
+        let edges = frame.windows(2).filter(|pair| pair[0] != pair[1]).count();
+        let expected = 2.0 * BEEP_HZ / TIMER_HZ as f32;
+        assert!((edges as f32 - expected).abs() <= 2.0, "{edges} edges, expected about {expected}");
+    }
+    for frame in &frames[9..] {
+        assert!(frame.iter().all(|s| *s == 0), "silence expected");
+    }
+}
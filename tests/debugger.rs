@@ -0,0 +1,116 @@
+use rschip8::prelude::*;
+
+// Stores V0-V3 at 0x300 with `LD [I], V3` at 0x206, then counts in V5.
+fn rom() -> Vec<u8> {
+    assemble("
+        LD V3, 4
+        LD I, 0x300
+        ADD V5, 1
+        LD [I], V3
+    loop:
+        ADD V5, 1
+        JP loop
+    ").unwrap()
+}
+
+fn run_to_break(debugger: &mut Debugger, cpu: &mut Cpu) -> Option<Break> {
+    let mut scheduler = Scheduler::default();
+    for _ in 0..10 {
+        if let Some(reason) = debugger.frame(&mut scheduler, cpu).unwrap() {
+            return Some(reason);
+        }
+    }
+    None
+}
+
+#[test]
+fn write_watch_hits_on_a_register_store() {
+    let mut cpu = Cpu::new(&rom()).unwrap();
+    let mut debugger = Debugger::new();
+    debugger.add_watch("r:0x300-0x303".parse().unwrap());
+    let index = debugger.add_watch("w:0x303".parse().unwrap());
+
+    assert_eq!(run_to_break(&mut debugger, &mut cpu), Some(Break::Watchpoint { index, pc: 0x206 }));
+    assert!(debugger.paused());
+    // The break comes after the store, with nothing run past it.
+    assert_eq!(cpu.bus().peek_slice(0x300..0x304), [0, 0, 0, 4]);
+    assert_eq!((cpu.pc(), cpu.v()[5]), (0x208, 1));
+}
+
+#[test]
+fn watches_outside_the_stored_range_stay_quiet() {
+    let mut cpu = Cpu::new(&rom()).unwrap();
+    let mut debugger = Debugger::new();
+    debugger.add_watch("w:0x304-0x3ff".parse().unwrap());
+    debugger.add_watch("r:0x300".parse().unwrap());
+
+    assert_eq!(run_to_break(&mut debugger, &mut cpu), None);
+    assert_eq!(cpu.bus().peek(0x303), 4);
+}
+
+#[test]
+fn register_watch_hits_on_a_matching_value() {
+    let mut cpu = Cpu::new(&rom()).unwrap();
+    let mut debugger = Debugger::new();
+    let index = debugger.add_watch("v5==0x03".parse().unwrap());
+
+    assert_eq!(run_to_break(&mut debugger, &mut cpu), Some(Break::Watchpoint { index, pc: 0x208 }));
+    assert_eq!(cpu.v()[5], 3);
+}
+
+#[test]
+fn steps_past_draws_under_vip_quirks() {
+    let rom = assemble("
+        LD I, 0x300
+        DRW V0, V0, 1
+        DRW V0, V0, 1
+        LD V1, 7
+        EXIT
+    ").unwrap();
+    let mut cpu = Cpu::new(&rom).unwrap();
+    cpu.set_quirks(Quirks::vip());
+    cpu.timers_mut().delay = 10;
+    let mut debugger = Debugger::new();
+
+    // Each draw waits for the vertical blank, which a step brings forward.
+    for pc in [0x202, 0x204, 0x206, 0x208] {
+        assert!(debugger.step(&mut cpu).unwrap());
+        assert_eq!(cpu.pc(), pc);
+        assert_eq!(debugger.last_break(), Some(Break::Step));
+    }
+    assert_eq!(cpu.v()[1], 7);
+    assert_eq!(cpu.timers().delay, 8);
+}
+
+#[test]
+fn steps_wait_for_a_key() {
+    let mut cpu = Cpu::new(&assemble("LD V2, K\nLD V3, 1\n").unwrap()).unwrap();
+    let mut debugger = Debugger::new();
+    assert!(debugger.step(&mut cpu).unwrap());
+
+    // Nothing runs, and nothing claims to have, until a key arrives.
+    debugger.pause();
+    let before = debugger.last_break();
+    assert!(!debugger.step(&mut cpu).unwrap());
+    assert_eq!((cpu.pc(), debugger.last_break()), (0x202, before));
+
+    cpu.press(9);
+    cpu.release(9);
+    assert!(debugger.step(&mut cpu).unwrap());
+    assert_eq!((cpu.pc(), cpu.v()[2], cpu.v()[3]), (0x204, 9, 1));
+}
+
+#[test]
+fn a_waiting_step_ends_after_the_key() {
+    let mut cpu = Cpu::new(&assemble("LD V2, K\nLD V3, 1\nLD V4, 1\n").unwrap()).unwrap();
+    cpu.set_key_wait_mode(KeyWaitMode::Modern);
+    let mut debugger = Debugger::new();
+    debugger.step(&mut cpu).unwrap();
+
+    debugger.step_after_wait(&cpu);
+    let mut scheduler = Scheduler::default();
+    assert_eq!(debugger.frame(&mut scheduler, &mut cpu).unwrap(), None);
+    cpu.press(6);
+    assert_eq!(debugger.frame(&mut scheduler, &mut cpu).unwrap(), Some(Break::Step));
+    assert_eq!((cpu.pc(), cpu.v()[2], cpu.v()[3], cpu.v()[4]), (0x204, 6, 1, 0));
+}
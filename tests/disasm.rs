@@ -0,0 +1,75 @@
+use std::fs;
+use std::path::Path;
+
+use rschip8::prelude::*;
+
+#[test]
+fn labels_jump_call_and_data_targets() {
+    let rom = assemble("
+    start:
+        LD I, sprite
+        CALL draw
+        SE V0, 1
+        JP start
+        EXIT
+    draw:
+        DRW V0, V1, 2
+        RET
+    sprite:
+        db 0xf0, 0x90
+    ").unwrap();
+
+    assert_eq!(Disassembly::new(&rom).unwrap().to_string(), "\
+label_200:
+    LD I, data_20e          ; 200
+    CALL sub_20a            ; 202
+    SE V0, 0x01             ; 204
+    JP label_200            ; 206
+    EXIT                    ; 208
+sub_20a:
+    DRW V0, V1, 2           ; 20a
+    RET                     ; 20c
+data_20e:
+    db 0xf0                 ; 20e ####....
+    db 0x90                 ; 20f #..#....
+");
+}
+
+// Bytes that decode fine are still data unless something reaches them.
+#[test]
+fn only_reachable_bytes_are_code() {
+    let rom = assemble("
+        JP over
+        LD V0, 5
+        ADD V0, 1
+    over:
+        SKP V0
+        LD I, LONG 0x1234
+        RET
+        dw 0x00e0
+    ").unwrap();
+    let disassembly = Disassembly::new(&rom).unwrap();
+
+    let code = (0x200..0x200 + rom.len()).filter(|addr| disassembly.is_code(*addr)).collect::<Vec<_>>();
+    // The skip steps over all four bytes of the long load.
+    assert_eq!(code, [0x200, 0x206, 0x208, 0x20c]);
+    assert!(disassembly.to_string().contains("    db 0x60                 ; 202"));
+}
+
+#[test]
+fn fixtures_reassemble_to_the_same_rom() {
+    let dir = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/octo");
+    let mut roms = fs::read_dir(&dir).unwrap()
+        .map(|entry| entry.unwrap().path())
+        .filter(|path| path.extension().is_some_and(|ext| ext == "ch8"))
+        .collect::<Vec<_>>();
+    roms.sort();
+    assert!(!roms.is_empty());
+
+    for path in roms {
+        let rom = fs::read(&path).unwrap();
+        let source = Disassembly::new(&rom).unwrap().to_string();
+        let bytes = assemble(&source).unwrap_or_else(|e| panic!("{}: {e}", path.display()));
+        assert_eq!(bytes, rom, "{}", path.display());
+    }
+}
@@ -0,0 +1,195 @@
+#![cfg(feature = "dynarec")]
+
+use std::fs;
+use std::path::Path;
+
+use rschip8::prelude::*;
+
+const CLOCK_HZ: u32 = 2000;
+
+fn presets() -> impl Iterator<Item = (&'static str, Quirks)> {
+    Quirks::PRESETS.into_iter().map(|name| (name, name.parse().unwrap()))
+}
+
+fn new_cpu(rom: &[u8], quirks: Quirks) -> Cpu {
+    let mut cpu = Cpu::new(rom).unwrap();
+    cpu.set_seed(7);
+    cpu.set_quirks(quirks);
+    cpu
+}
+
+// Presses a different key every few frames, so `SKP` and `LD Vx, K` take
+// both paths.
+fn keys(frame: u64, cpu: &mut Cpu) {
+    let key = u8::try_from(frame / 7 % 16).unwrap();
+    match frame % 7 {
+        0 => cpu.press(key),
+        3 => cpu.release(key),
+        _ => {}
+    }
+}
+
+// Runs `rom` on `Scheduler::frame` and on `Dynarec::frame` side by side,
+// comparing the whole machine after every frame.
+fn compare(name: &str, rom: &[u8], quirks: Quirks, frames: u64) {
+    let mut expected = new_cpu(rom, quirks);
+    let mut actual = new_cpu(rom, quirks);
+    let mut scheduler = Scheduler::new(CLOCK_HZ);
+    let mut compiled = Scheduler::new(CLOCK_HZ);
+    let mut dynarec = Dynarec::new();
+
+    for frame in 0..frames {
+        keys(frame, &mut expected);
+        keys(frame, &mut actual);
+        let result = scheduler.frame(&mut expected);
+        assert_eq!(dynarec.frame(&mut compiled, &mut actual), result, "{name}: frame {frame}");
+        assert_eq!(
+            (actual.pc(), actual.i(), actual.v(), actual.cycles()),
+            (expected.pc(), expected.i(), expected.v(), expected.cycles()),
+            "{name}: frame {frame}"
+        );
+        assert!(actual.save_state() == expected.save_state(), "{name}: state differs at frame {frame}");
+
+        if result.is_err() || expected.halted() {
+            break;
+        }
+    }
+}
+
+#[test]
+fn matches_the_interpreter_on_test_roms() {
+    let dir = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/octo");
+    let mut roms = fs::read_dir(&dir).unwrap()
+        .map(|entry| entry.unwrap().path())
+        .filter(|path| path.extension().is_some_and(|ext| ext == "ch8"))
+        .collect::<Vec<_>>();
+    roms.sort();
+    assert!(!roms.is_empty());
+
+    for path in roms {
+        let rom = fs::read(&path).unwrap();
+        for (preset, quirks) in presets() {
+            compare(&format!("{} ({preset})", path.display()), &rom, quirks, 300);
+        }
+    }
+}
+
+// VF as the operand of the instructions that also set it.
+#[test]
+fn matches_the_interpreter_with_vf_operands() {
+    let rom = assemble("
+        start:
+            LD VF, 0xf0
+            LD V1, 0x30
+            ADD VF, V1
+            ADD V1, VF
+            SUB VF, V1
+            SUBN VF, V1
+            SHR VF, V1
+            SHL V1, VF
+            OR VF, V1
+            LD I, LONG 0xfff0
+            LD VF, 0x20
+            ADD I, VF
+            ADD V2, 1
+            SE V2, 0
+            JP start
+            EXIT
+    ").unwrap();
+
+    for (preset, quirks) in presets() {
+        compare(&format!("vf operands ({preset})"), &rom, quirks, 60);
+    }
+}
+
+// A loop that rewrites the byte added in its own body, and the address in a
+// long `LD I` it then stores through.
+#[test]
+fn matches_the_interpreter_on_self_modifying_code() {
+    let rom = assemble("
+        start:
+            ADD V0, 3
+            LD I, patch + 1
+            LD [I], V0
+        patch:
+            ADD V1, 0
+            LD I, far + 3
+            LD [I], V0
+        far:
+            LD I, LONG 0x0300
+            LD [I], V1
+            SE V0, 0xff
+            JP start
+            EXIT
+    ").unwrap();
+
+    for (preset, quirks) in presets() {
+        compare(&format!("self-modifying ({preset})"), &rom, quirks, 120);
+    }
+}
+
+#[test]
+fn recompiles_when_the_quirks_change() {
+    let rom = assemble("
+        start:
+            LD V1, 0x81
+            SHR V0, V1
+            JP start
+    ").unwrap();
+
+    let mut cpu = new_cpu(&rom, Quirks::vip());
+    let mut dynarec = Dynarec::new();
+    assert_eq!(dynarec.run(&mut cpu, 3).unwrap(), 3);
+    assert_eq!(cpu.v()[0], 0x40);
+
+    // The shift quirk shifts V0 itself rather than V1.
+    cpu.set_quirks(Quirks::schip());
+    dynarec.run(&mut cpu, 3).unwrap();
+    assert_eq!(cpu.v()[0], 0x20);
+}
+
+// Programs made of instructions that mostly decode and branch within the
+// ROM, plus the odd random word, looping back to the start at the end.
+fn random_rom(rng: &mut Xorshift) -> Vec<u8> {
+    const WORDS: u16 = 48;
+
+    let mut rom = Vec::new();
+    for _ in 0..WORDS {
+        let x = u16::from(rng.next_u8() & 0xf);
+        let y = u16::from(rng.next_u8() & 0xf);
+        let nn = u16::from(rng.next_u8());
+        let target = 0x200 + u16::from(rng.next_u8()) % WORDS * 2;
+        let word = match rng.next_u8() % 32 {
+            0..=6   => 0x6000 | x << 8 | nn,
+            7..=10  => 0x7000 | x << 8 | nn,
+            11..=17 => 0x8000 | x << 8 | y << 4 | [0, 1, 2, 3, 4, 5, 6, 7, 0xe][usize::from(nn % 9)],
+            18      => 0xa200 | nn,
+            19      => 0xf01e | x << 8,
+            20..=21 => [0x3000, 0x4000][usize::from(nn & 1)] | x << 8 | nn,
+            22      => [0x5000, 0x9000][usize::from(nn & 1)] | x << 8 | y << 4,
+            23..=24 => 0x1000 | target,
+            25      => 0x2000 | target,
+            26      => 0xd000 | x << 8 | y << 4 | nn & 0xf,
+            27      => [0xf033, 0xf055, 0xf065, 0x5002 | y << 4, 0x5003 | y << 4][usize::from(nn % 5)] | x << 8,
+            28      => [0xc000 | nn, 0xf007, 0xf015, 0xf018, 0xe09e, 0xe0a1][usize::from(nn % 6)] | x << 8,
+            29      => [0xf000, 0x00e0, 0x00fb, 0xf00a | x << 8][usize::from(nn % 4)],
+            30      => 0x00ee,
+            _       => nn << 8 | u16::from(rng.next_u8()),
+        };
+        rom.extend_from_slice(&word.to_be_bytes());
+    }
+    rom.extend_from_slice(&[0x12, 0x00]);
+
+    rom
+}
+
+#[test]
+fn matches_the_interpreter_on_random_programs() {
+    let mut rng = Xorshift::new(1);
+    for n in 0..200 {
+        let rom = random_rom(&mut rng);
+        for (preset, quirks) in presets() {
+            compare(&format!("random #{n} ({preset})"), &rom, quirks, 60);
+        }
+    }
+}
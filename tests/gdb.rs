@@ -0,0 +1,183 @@
+use std::io::{BufReader, Read, Write};
+use std::net::TcpStream;
+use std::thread;
+use std::time::Duration;
+
+use rschip8::prelude::*;
+
+// LD V0, 5; LD V1, 7; ADD V0, V1; LD I, 0x300; LD [I], V0; JP 0x20a
+const ROM: [u8; 12] = [
+    0x60, 0x05, 0x61, 0x07, 0x80, 0x14, 0xa3, 0x00, 0xf0, 0x55, 0x12, 0x0a,
+];
+
+struct Client {
+    stream: TcpStream,
+    reader: BufReader<TcpStream>,
+}
+
+impl Client {
+    fn connect(port: u16) -> Self {
+        let stream = TcpStream::connect(("127.0.0.1", port)).unwrap();
+        stream.set_read_timeout(Some(Duration::from_secs(5))).unwrap();
+        let reader = BufReader::new(stream.try_clone().unwrap());
+        Self { stream, reader }
+    }
+
+    fn byte(&mut self) -> u8 {
+        let mut b = [0];
+        self.reader.read_exact(&mut b).unwrap();
+        b[0]
+    }
+
+    fn reply(&mut self) -> String {
+        while self.byte() != b'$' {}
+        let mut data = Vec::new();
+        loop {
+            match self.byte() {
+                b'#' => break,
+                b => data.push(b),
+            }
+        }
+        let sum = [self.byte(), self.byte()];
+        let expected = data.iter().fold(0u8, |s, b| s.wrapping_add(*b));
+        assert_eq!(std::str::from_utf8(&sum).unwrap(), format!("{expected:02x}"));
+        self.stream.write_all(b"+").unwrap();
+        String::from_utf8(data).unwrap()
+    }
+
+    fn send(&mut self, data: &str) {
+        let sum = data.bytes().fold(0u8, |s, b| s.wrapping_add(b));
+        self.stream.write_all(format!("${data}#{sum:02x}").as_bytes()).unwrap();
+        assert_eq!(self.byte(), b'+');
+    }
+
+    fn request(&mut self, data: &str) -> String {
+        self.send(data);
+        self.reply()
+    }
+}
+
+#[test]
+fn scripted_session() {
+    let mut gdb = GdbStub::bind("127.0.0.1:0").unwrap();
+    let port = gdb.local_addr().unwrap().port();
+    let server = thread::spawn(move || {
+        let mut cpu = Cpu::new(&ROM).unwrap();
+        gdb.serve(&mut cpu, &mut Scheduler::new(DEFAULT_CLOCK_HZ), &mut Debugger::new()).unwrap();
+        cpu
+    });
+
+    let mut client = Client::connect(port);
+    assert!(client.request("qSupported:swbreak+").contains("PacketSize"));
+    assert_eq!(client.request("?"), "S05");
+
+    let registers = client.request("g");
+    assert_eq!(registers.len(), 23 * 2);
+    assert_eq!(&registers[36..40], "0200");
+
+    assert_eq!(client.request("Z0,204,2"), "OK");
+    assert_eq!(client.request("c"), "S05");
+    assert_eq!(client.request("p11"), "0204");
+    assert_eq!(client.request("p0"), "05");
+    assert_eq!(client.request("p1"), "07");
+
+    assert_eq!(client.request("s"), "S05");
+    assert_eq!(client.request("p0"), "0c");
+    assert_eq!(client.request("z0,204,2"), "OK");
+
+    assert_eq!(client.request("Z2,300,1"), "OK");
+    assert_eq!(client.request("c"), "S05");
+    assert_eq!(client.request("p11"), "020a");
+    assert_eq!(client.request("p10"), "0301");
+    assert_eq!(client.request("m300,1"), "0c");
+    assert_eq!(client.request("z2,300,1"), "OK");
+    assert_eq!(client.request("Z2,ffffffffffffffff,2"), "E01");
+    assert_eq!(client.request("Z4,fffffffffffffffe,3"), "E01");
+    assert_eq!(client.request("Z3,ffffffffffffffff,1"), "OK");
+    assert_eq!(client.request("z3,ffffffffffffffff,1"), "OK");
+
+    assert_eq!(client.request("M300,2:abcd"), "OK");
+    assert_eq!(client.request("m300,2"), "abcd");
+    assert_eq!(client.request("mffff,2"), "E01");
+    assert_eq!(client.request("P0=42"), "OK");
+    assert_eq!(client.request("p0"), "42");
+
+    client.send("c");
+    thread::sleep(Duration::from_millis(50));
+    client.stream.write_all(&[0x03]).unwrap();
+    assert_eq!(client.reply(), "S02");
+    assert_eq!(client.request("p11"), "020a");
+
+    assert_eq!(client.request("D"), "OK");
+    let cpu = server.join().unwrap();
+    assert_eq!(cpu.v()[0], 0x42);
+    assert_eq!(cpu.bus().peek_slice(0x300..0x302), [0xab, 0xcd]);
+}
+
+fn serve(rom: &[u8], quirks: Quirks) -> (Client, thread::JoinHandle<Cpu>) {
+    let mut gdb = GdbStub::bind("127.0.0.1:0").unwrap();
+    let port = gdb.local_addr().unwrap().port();
+    let mut cpu = Cpu::new(rom).unwrap();
+    cpu.set_quirks(quirks);
+    let server = thread::spawn(move || {
+        gdb.serve(&mut cpu, &mut Scheduler::new(DEFAULT_CLOCK_HZ), &mut Debugger::new()).unwrap();
+        cpu
+    });
+    (Client::connect(port), server)
+}
+
+#[test]
+fn steps_past_draws_and_waits_for_keys() {
+    let rom = assemble("
+        DRW V0, V0, 1
+        DRW V0, V0, 1
+        LD V0, K
+        JP 0x206
+    ").unwrap();
+    let (mut client, server) = serve(&rom, Quirks::vip());
+
+    assert_eq!(client.request("s"), "S05");
+    assert_eq!(client.request("s"), "S05");
+    assert_eq!(client.request("p11"), "0204");
+    assert_eq!(client.request("s"), "S05");
+    assert_eq!(client.request("p11"), "0206");
+
+    // Waiting for a key, the step only ends once one arrives, so no stop
+    // reply comes until the interrupt.
+    client.send("s");
+    thread::sleep(Duration::from_millis(50));
+    client.stream.write_all(&[0x03]).unwrap();
+    assert_eq!(client.reply(), "S02");
+    assert_eq!(client.request("p11"), "0206");
+
+    assert_eq!(client.request("D"), "OK");
+    server.join().unwrap();
+}
+
+#[test]
+fn serves_a_target_description() {
+    let (mut client, server) = serve(&ROM, Quirks::default());
+    assert!(client.request("qSupported").contains("qXfer:features:read+"));
+
+    let mut xml = String::new();
+    loop {
+        let reply = client.request(&format!("qXfer:features:read:target.xml:{:x},40", xml.len()));
+        let (kind, data) = reply.split_at(1);
+        xml.push_str(data);
+        if kind == "l" {
+            break;
+        }
+        assert_eq!(kind, "m");
+    }
+    assert!(xml.starts_with("<?xml"));
+    assert!(xml.ends_with("</target>\n"));
+    let names = xml.split("name=\"").skip(2).map(|s| &s[..s.find('"').unwrap()]).collect::<Vec<_>>();
+    assert_eq!(names, [
+        "v0", "v1", "v2", "v3", "v4", "v5", "v6", "v7", "v8", "v9", "va", "vb", "vc", "vd", "ve", "vf",
+        "i", "pc", "sp", "dt", "st",
+    ]);
+    assert_eq!(client.request("qXfer:features:read:other.xml:0,40"), "E00");
+
+    assert_eq!(client.request("D"), "OK");
+    server.join().unwrap();
+}
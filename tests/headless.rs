@@ -0,0 +1,40 @@
+use rschip8::prelude::*;
+
+#[test]
+fn input_scripts_sort_events_by_frame() {
+    let script = InputScript::parse("\
+        # title screen
+        30 press a
+        10 press 5
+        30 release A   # same frame, kept in order
+        10 release 5
+    ").unwrap();
+
+    assert_eq!(script.events_at(10).collect::<Vec<_>>(), [KeyEvent::Press(5), KeyEvent::Release(5)]);
+    assert_eq!(script.events_at(30).collect::<Vec<_>>(), [KeyEvent::Press(0xa), KeyEvent::Release(0xa)]);
+    assert_eq!(script.events_at(20).count(), 0);
+}
+
+#[test]
+fn input_script_errors_name_the_line() {
+    for (text, error) in [
+        ("10 press", "line 1: expected `<frame> press|release <hex key>`"),
+        ("\n10 press 5 now", "line 2: expected `<frame> press|release <hex key>`"),
+        ("-1 press 5", "line 1: frame: invalid digit found in string"),
+        ("10 press 10", "line 1: keypad key must be 0-F"),
+        ("# ok\n10 tap 5", "line 2: expected press or release, got tap"),
+    ] {
+        assert_eq!(InputScript::parse(text).unwrap_err(), error, "{text:?}");
+    }
+}
+
+#[test]
+fn scripts_drive_a_headless_run() {
+    let cpu = Cpu::new(&assemble("LD V0, K\nSE V0, 9\nJP 0x200\nEXIT\n").unwrap()).unwrap();
+    let mut runner = Headless::new(cpu, Scheduler::default());
+    runner.input = InputScript::parse("5 press 3\n6 release 3\n20 press 9\n21 release 9\n").unwrap();
+
+    let limits = Limits { frames: Some(100), ..Limits::default() };
+    assert_eq!(runner.run(&limits).unwrap(), StopReason::Exit);
+    assert_eq!(runner.cpu.v()[0], 9);
+}
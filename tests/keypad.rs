@@ -0,0 +1,117 @@
+use rschip8::prelude::*;
+
+#[test]
+fn keymap_rebinds_on_top_of_qwerty() {
+    let keymap = KeyMap::parse("# arrows\nW = 5\n  j = a  # fire\n\n").unwrap();
+    assert_eq!(keymap.get('w'), Some(5));
+    assert_eq!(keymap.get('J'), Some(0xa));
+    // Each keypad key has one host key, so the old bindings are gone.
+    assert_eq!(keymap.get('z'), None);
+    assert_eq!(keymap.get('1'), Some(1));
+}
+
+#[test]
+fn keymap_errors_name_the_line() {
+    for (text, error) in [
+        ("w 5", "line 1: expected `<host key> = <hex key>`"),
+        ("\nup = 5", "line 2: host key must be a single character"),
+        (" = 5", "line 1: host key must be a single character"),
+        ("w = 10", "line 1: keypad key must be 0-F"),
+        ("# ok\nw = g", "line 2: keypad key must be 0-F"),
+    ] {
+        assert_eq!(KeyMap::parse(text).unwrap_err(), error, "{text:?}");
+    }
+}
+
+// `LD V3, K`, then a marker so it shows whether execution moved on.
+fn waiting_cpu(mode: KeyWaitMode) -> Cpu {
+    let mut cpu = Cpu::new(&assemble("LD V3, K\nLD V4, 1\nEXIT\n").unwrap()).unwrap();
+    cpu.set_key_wait_mode(mode);
+    let mut scheduler = Scheduler::default();
+    scheduler.frame(&mut cpu).unwrap();
+    assert!(cpu.waiting_key());
+    cpu
+}
+
+fn resumes(cpu: &mut Cpu) -> bool {
+    Scheduler::default().frame(cpu).unwrap();
+    cpu.v()[4] == 1
+}
+
+#[test]
+fn vip_key_wait_needs_a_press_and_release() {
+    let mut cpu = waiting_cpu(KeyWaitMode::Vip);
+    assert!(!resumes(&mut cpu));
+
+    // The press alone stores nothing, and only releasing that same key
+    // ends the wait.
+    cpu.press(7);
+    assert!(cpu.waiting_key() && !resumes(&mut cpu));
+    assert_eq!(cpu.v()[3], 0);
+    cpu.press(2);
+    cpu.release(2);
+    assert!(cpu.waiting_key() && !resumes(&mut cpu));
+
+    cpu.release(7);
+    assert!(!cpu.waiting_key());
+    assert_eq!(cpu.v()[3], 7);
+    assert!(resumes(&mut cpu));
+}
+
+#[test]
+fn modern_key_wait_returns_on_press() {
+    let mut cpu = waiting_cpu(KeyWaitMode::Modern);
+    cpu.press(0xb);
+    assert!(!cpu.waiting_key());
+    assert_eq!(cpu.v()[3], 0xb);
+    assert!(resumes(&mut cpu));
+
+    // The release afterwards has nothing left to finish.
+    cpu.release(0xb);
+    assert_eq!(cpu.v()[3], 0xb);
+}
+
+#[test]
+fn held_keys_do_not_end_a_wait() {
+    for mode in [KeyWaitMode::Vip, KeyWaitMode::Modern] {
+        let mut cpu = Cpu::new(&assemble("LD V3, K\nLD V4, 1\nEXIT\n").unwrap()).unwrap();
+        cpu.set_key_wait_mode(mode);
+        cpu.press(5);
+        Scheduler::default().frame(&mut cpu).unwrap();
+        assert!(cpu.waiting_key(), "{mode:?}");
+
+        // Pressing it again without a release is not a new edge.
+        cpu.press(5);
+        assert!(cpu.waiting_key(), "{mode:?}");
+        cpu.release(5);
+        assert!(cpu.waiting_key(), "{mode:?}");
+    }
+}
+
+#[test]
+fn key_wait_states() {
+    let mut wait = KeyWait::Press { x: 3 };
+    assert_eq!(wait.release(7), None);
+    assert_eq!(wait.press(7, KeyWaitMode::Vip), None);
+    assert_eq!(wait, KeyWait::Release { x: 3, key: 7 });
+    assert_eq!(wait.press(8, KeyWaitMode::Vip), None);
+    assert_eq!(wait.release(8), None);
+    assert_eq!(wait.release(7), Some((3, 7)));
+    assert!(!wait.waiting());
+
+    let mut wait = KeyWait::Press { x: 0xe };
+    assert_eq!(wait.press(1, KeyWaitMode::Modern), Some((0xe, 1)));
+    assert_eq!(wait, KeyWait::Idle);
+    assert_eq!(wait.press(2, KeyWaitMode::Modern), None);
+}
+
+#[test]
+fn keymap_rejects_keys_past_f() {
+    let mut keymap = KeyMap::default();
+    assert_eq!(keymap.set('p', 0x15).unwrap_err(), "keypad key 0x15 is out of range, expected 0-F");
+    // Nothing was unbound on the way.
+    assert_eq!(keymap, KeyMap::default());
+
+    keymap.set('p', 0x5).unwrap();
+    assert_eq!((keymap.get('p'), keymap.get('w')), (Some(5), None));
+}
@@ -0,0 +1,77 @@
+use rschip8::prelude::*;
+
+const FRAMES: u64 = 90;
+
+// Waits for keys, mixes in `RND` and draws, so a replay with the wrong input
+// or seed ends up somewhere else.
+fn rom() -> Vec<u8> {
+    assemble("
+    loop:
+        LD V0, K
+        RND V1, 0x1f
+        ADD V2, V0
+        LD F, V0
+        DRW V1, V2, 5
+        JP loop
+    ").unwrap()
+}
+
+fn record() -> (Movie, SaveState) {
+    let mut cpu = Cpu::new(&rom()).unwrap();
+    cpu.set_seed(1234);
+    let mut runner = Headless::new(cpu, Scheduler::default());
+    runner.recording = Some(Movie::new(&runner.cpu, runner.scheduler.clock_hz()));
+    for (frame, key) in (5..).step_by(10).zip([3, 7, 0xa, 1, 0xf, 9, 2]) {
+        runner.input.push(frame, KeyEvent::Press(key));
+        runner.input.push(frame + 4, KeyEvent::Release(key));
+    }
+
+    let limits = Limits { frames: Some(FRAMES), ..Limits::default() };
+    runner.run(&limits).unwrap();
+    let mut movie = runner.recording.take().unwrap();
+    movie.finish(&runner.cpu.video);
+    (movie, runner.cpu.save_state())
+}
+
+#[test]
+fn replay_reaches_the_recorded_state() {
+    let (movie, recorded) = record();
+    assert_eq!(movie.frames.len(), usize::try_from(FRAMES).unwrap());
+
+    let movie = Movie::decode(&movie.encode()).unwrap();
+    let mut cpu = Cpu::new(&rom()).unwrap();
+    cpu.set_seed(1);
+    movie.start(&mut cpu).unwrap();
+    let mut runner = Headless::new(cpu, Scheduler::new(movie.clock_hz));
+    runner.movie = Some(movie);
+
+    let limits = Limits { frames: Some(FRAMES), ..Limits::default() };
+    runner.run(&limits).unwrap();
+    assert_eq!(runner.movie.as_ref().unwrap().verify(&runner.cpu.video), Some(true));
+    assert!(runner.cpu.save_state() == recorded, "replay ended in a different state");
+}
+
+#[test]
+fn encoding_round_trips() {
+    let (movie, _) = record();
+    assert_eq!(Movie::decode(&movie.encode()).unwrap(), movie);
+
+    let unfinished = Movie { video_hash: None, ..movie };
+    assert_eq!(Movie::decode(&unfinished.encode()).unwrap(), unfinished);
+}
+
+#[test]
+fn bad_movies_are_rejected() {
+    let (movie, _) = record();
+    let data = movie.encode();
+
+    assert_eq!(Movie::decode(&data[..data.len() - 1]).unwrap_err(), "movie is truncated");
+    assert_eq!(Movie::decode(b"C8SS").unwrap_err(), "not a movie file");
+    let mut newer = data.clone();
+    newer[4] = 99;
+    assert_eq!(Movie::decode(&newer).unwrap_err(), "unsupported movie version 99");
+
+    let mut other = Cpu::new(&assemble("CLS").unwrap()).unwrap();
+    let error = movie.start(&mut other).unwrap_err();
+    assert!(error.starts_with("movie is for ROM"), "{error}");
+}
@@ -0,0 +1,82 @@
+use std::fs;
+use std::path::Path;
+
+use rschip8::prelude::*;
+
+const FRAMES: u64 = 120;
+
+// Every `tests/octo/<name>.8o` against the `<name>.ch8` next to it, and each
+// ROM has to run for `FRAMES` frames without an error. The `.ch8` files are
+// this compiler's own output, checked in so changes to it show up; they were
+// not built by Octo. `conditions_use_octos_encodings` pins what Octo emits.
+#[test]
+fn compiles_the_fixtures_unchanged() {
+    let dir = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/octo");
+    let mut sources = fs::read_dir(&dir).unwrap()
+        .map(|entry| entry.unwrap().path())
+        .filter(|path| path.extension().is_some_and(|ext| ext == "8o"))
+        .collect::<Vec<_>>();
+    sources.sort();
+    assert!(!sources.is_empty());
+
+    for source in sources {
+        let rom = compile_octo_file(&source.to_string_lossy()).unwrap_or_else(|e| panic!("{e}"));
+        let expected = fs::read(source.with_extension("ch8")).unwrap();
+        assert_eq!(rom, expected, "{}", source.display());
+
+        let mut cpu = Cpu::new(&rom).unwrap();
+        cpu.set_quirks(Quirks::xochip());
+        let mut runner = Headless::new(cpu, Scheduler::default());
+        let limits = Limits { frames: Some(FRAMES), ..Limits::default() };
+        runner.run(&limits).unwrap_or_else(|e| panic!("{}: {e}", source.display()));
+    }
+}
+
+// Octo's expansions, written out by hand. Under `then` the condition skips
+// the statement when it does not hold; `begin` negates it and jumps over
+// the block.
+#[test]
+fn conditions_use_octos_encodings() {
+    for (condition, expected) in [
+        ("v1 == 2",    &[0x41, 0x02][..]),
+        ("v1 != 2",    &[0x31, 0x02]),
+        ("v1 == v2",   &[0x91, 0x20]),
+        ("v1 != v2",   &[0x51, 0x20]),
+        ("v1 key",     &[0xe1, 0xa1]),
+        ("v1 -key",    &[0xe1, 0x9e]),
+        ("v1 < v2",    &[0x8f, 0x20, 0x8f, 0x17, 0x3f, 0x01]),
+        ("v1 > v2",    &[0x8f, 0x20, 0x8f, 0x15, 0x3f, 0x01]),
+        ("v1 <= v2",   &[0x8f, 0x20, 0x8f, 0x15, 0x4f, 0x01]),
+        ("v1 >= v2",   &[0x8f, 0x20, 0x8f, 0x17, 0x4f, 0x01]),
+        ("v1 < 7",     &[0x6f, 0x07, 0x8f, 0x17, 0x3f, 0x01]),
+    ] {
+        let rom = compile_octo(&format!(": main\n\tif {condition} then v0 := 1\n")).unwrap();
+        assert_eq!(rom, [expected, &[0x60, 0x01]].concat(), "if {condition} then");
+    }
+
+    for (condition, expected) in [
+        ("v1 == 2",    &[0x31, 0x02][..]),
+        ("v1 -key",    &[0xe1, 0xa1]),
+        ("v1 < v2",    &[0x8f, 0x20, 0x8f, 0x17, 0x4f, 0x01]),
+        ("v1 >= 3",    &[0x6f, 0x03, 0x8f, 0x17, 0x3f, 0x01]),
+    ] {
+        let rom = compile_octo(&format!(": main\n\tif {condition} begin v0 := 1 end\n")).unwrap();
+        let end = u16::try_from(0x200 + expected.len() + 4).unwrap() | 0x1000;
+        assert_eq!(rom, [expected, &end.to_be_bytes(), &[0x60, 0x01]].concat(), "if {condition} begin");
+    }
+}
+
+#[test]
+fn errors_point_at_the_token() {
+    let error = compile_octo(": main\n\tv0 := 300\n").unwrap_err();
+    assert_eq!((error.line, error.col), (2, 8));
+
+    let error = compile_octo(": main\n\tjump nowhere\n").unwrap_err();
+    assert_eq!((error.line, error.col, error.message.as_str()), (2, 7, "undefined name `nowhere`"));
+
+    let error = compile_octo(": start\n\tclear\n").unwrap_err();
+    assert_eq!(error.message, "program has no `: main`");
+
+    let error = compile_octo(":calc X { 1 << 70 }\n: main\n").unwrap_err();
+    assert_eq!((error.line, error.col, error.message.as_str()), (1, 13, "cannot shift by 70"));
+}
@@ -0,0 +1,52 @@
+use rschip8::prelude::*;
+
+// Every word that decodes to an instruction encodes back to itself, and
+// the instruction survives a trip through its bytes.
+#[test]
+fn every_word_round_trips() {
+    for word in 0..=u16::MAX {
+        let op = Op::decode(word);
+        if op == (Op::UNKNOWN {}) {
+            continue;
+        }
+
+        assert_eq!(op.encode(), word, "{op}");
+        assert_eq!(Op::decode(op.encode()), op, "{word:#06x}");
+
+        let bytes = op.to_bytes();
+        assert_eq!(bytes.len(), op.size(), "{op}");
+        assert_eq!(Op::from_bytes(&bytes), Some(op), "{word:#06x}");
+    }
+}
+
+#[test]
+fn unknown_encodes_as_an_unknown_word() {
+    assert_eq!(Op::decode(Op::UNKNOWN {}.encode()), Op::UNKNOWN {});
+}
+
+#[test]
+fn long_load_round_trips_every_address() {
+    for nnnn in 0..=usize::from(u16::MAX) {
+        let op = Op::LD_I_long { nnnn };
+        let bytes = op.to_bytes();
+        assert_eq!(bytes[..2], [0xf0, 0x00]);
+        assert_eq!(Op::from_bytes(&bytes), Some(op));
+    }
+
+    assert_eq!(Op::from_bytes(&[0xf0, 0x00, 0x12]), None);
+    assert_eq!(Op::from_bytes(&[0x60]), None);
+}
+
+// The mnemonics `Op` displays as assemble back to the same word.
+#[test]
+fn every_instruction_reassembles() {
+    for word in 0..=u16::MAX {
+        let op = Op::decode(word);
+        if op == (Op::UNKNOWN {}) {
+            continue;
+        }
+
+        let source = op.to_string();
+        assert_eq!(assemble(&source).unwrap(), op.to_bytes(), "{source}");
+    }
+}
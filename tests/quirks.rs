@@ -0,0 +1,150 @@
+use rschip8::prelude::*;
+
+fn presets() -> [(&'static str, Quirks); 3] {
+    [("vip", Quirks::vip()), ("chip48", Quirks::chip48()), ("xochip", Quirks::xochip())]
+}
+
+// Runs `source` to its `EXIT` under each of VIP, CHIP-48 and XO-CHIP and
+// returns the machines in that order.
+fn run(source: &str) -> [Cpu; 3] {
+    let rom = assemble(source).unwrap();
+    presets().map(|(name, quirks)| {
+        let mut cpu = Cpu::new(&rom).unwrap();
+        cpu.set_quirks(quirks);
+        let mut scheduler = Scheduler::default();
+        for _ in 0..10 {
+            scheduler.frame(&mut cpu).unwrap();
+        }
+        assert!(cpu.halted(), "{name}: did not reach EXIT");
+        cpu
+    })
+}
+
+fn lit(cpu: &Cpu, x: i32, y: i32) -> bool {
+    cpu.video.ram[cpu.video.map_idx(x, y)] != 0
+}
+
+#[test]
+fn vf_reset() {
+    let [vip, chip48, xochip] = run("
+        LD V0, 0x0c
+        LD V1, 0x0a
+        LD VF, 5
+        OR V0, V1
+        EXIT
+    ");
+    assert_eq!((vip.v()[0], vip.v()[0xf]), (0x0e, 0));
+    assert_eq!((chip48.v()[0], chip48.v()[0xf]), (0x0e, 5));
+    assert_eq!((xochip.v()[0], xochip.v()[0xf]), (0x0e, 5));
+}
+
+#[test]
+fn shift() {
+    let [vip, chip48, xochip] = run("
+        LD V0, 0x10
+        LD V1, 0x81
+        SHR V0, V1
+        LD V2, 0x10
+        SHL V2, V1
+        EXIT
+    ");
+    // VIP and XO-CHIP shift Vy into Vx, CHIP-48 shifts Vx in place.
+    assert_eq!((vip.v()[0], vip.v()[2], vip.v()[0xf]), (0x40, 0x02, 1));
+    assert_eq!((xochip.v()[0], xochip.v()[2], xochip.v()[0xf]), (0x40, 0x02, 1));
+    assert_eq!((chip48.v()[0], chip48.v()[2], chip48.v()[0xf]), (0x08, 0x20, 0));
+}
+
+#[test]
+fn jump() {
+    let [vip, chip48, xochip] = run("
+        LD V0, 4
+        LD V2, 8
+        JP V0, target
+    target:
+        LD V5, 1
+        EXIT
+        LD V5, 2
+        EXIT
+        LD V5, 3
+        EXIT
+    ");
+    // `target` is 0x206, so the jump is B206: VIP and XO-CHIP add V0, while
+    // CHIP-48 reads it as xnn + V2.
+    assert_eq!(vip.v()[5], 2);
+    assert_eq!(xochip.v()[5], 2);
+    assert_eq!(chip48.v()[5], 3);
+}
+
+#[test]
+fn load_store() {
+    let [vip, chip48, xochip] = run("
+        LD V0, 1
+        LD V1, 2
+        LD V2, 3
+        LD V3, 4
+        LD I, 0x300
+        LD [I], V2
+        LD V0, 0
+        LD V1, 0
+        LD V2, 0
+        LD V3, 0
+        LD I, 0x300
+        LD V2, [I]
+        EXIT
+    ");
+    // Both copy V0 through V2 inclusive, and no further.
+    for cpu in [&vip, &chip48, &xochip] {
+        assert_eq!(cpu.bus().peek_slice(0x300..0x304), [1, 2, 3, 0]);
+        assert_eq!(cpu.v()[..4], [1, 2, 3, 0]);
+    }
+    assert_eq!(vip.i(), 0x303);
+    assert_eq!(xochip.i(), 0x303);
+    assert_eq!(chip48.i(), 0x300);
+}
+
+#[test]
+fn clipping() {
+    let [vip, chip48, xochip] = run("
+        LD I, sprite
+        LD V0, 62
+        LD V1, 31
+        DRW V0, V1, 2
+        EXIT
+    sprite:
+        db 0xff, 0xff
+    ");
+    for cpu in [&vip, &chip48] {
+        assert!(lit(cpu, 63, 31));
+        assert!(!lit(cpu, 0, 31) && !lit(cpu, 0, 0) && !lit(cpu, 63, 0));
+    }
+    assert!(lit(&xochip, 63, 31) && lit(&xochip, 0, 31) && lit(&xochip, 0, 0) && lit(&xochip, 63, 0));
+}
+
+#[test]
+fn subn_writes_vx() {
+    for cpu in run("
+        LD V0, 3
+        LD V1, 10
+        SUBN V0, V1
+        EXIT
+    ") {
+        assert_eq!(cpu.v()[..2], [7, 10]);
+        assert_eq!(cpu.v()[0xf], 1);
+    }
+}
+
+#[test]
+fn parse() {
+    let quirks = Quirks::parse("preset = chip48\n\n  clipping = off  # wrap\nshift=0\n").unwrap();
+    assert_eq!(quirks, Quirks { clipping: false, shift: false, ..Quirks::chip48() });
+    assert_eq!("schip11".parse::<Quirks>().unwrap(), Quirks::schip());
+
+    for (text, error) in [
+        ("shift on", "line 1: expected `<quirk> = <value>`"),
+        ("# quirks\nshift = maybe", "line 2: expected on|off, got maybe"),
+        ("wrap = on", "line 1: unknown quirk wrap"),
+        ("preset = nes", "line 1: unknown quirks preset nes, expected vip|chip48|schip|xochip"),
+    ] {
+        assert_eq!(Quirks::parse(text).unwrap_err(), error, "{text:?}");
+    }
+}
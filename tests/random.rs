@@ -0,0 +1,63 @@
+use rschip8::prelude::*;
+
+fn sequence(rng: &mut Xorshift, len: usize) -> Vec<u64> {
+    (0..len).map(|_| rng.next_u64()).collect()
+}
+
+#[test]
+fn fixed_seeds_repeat() {
+    for seed in [0, 1, 42, u64::MAX] {
+        assert_eq!(sequence(&mut Xorshift::new(seed), 100), sequence(&mut Xorshift::new(seed), 100), "seed {seed}");
+    }
+    assert_ne!(sequence(&mut Xorshift::new(1), 4), sequence(&mut Xorshift::new(2), 4));
+}
+
+// splitmix64 then xorshift64*, pinned so recorded movies and save states
+// keep replaying after changes to the generator.
+#[test]
+fn known_sequence() {
+    let mut rng = Xorshift::new(0);
+    assert_eq!(sequence(&mut rng, 3), [8_916_199_331_640_804_048, 16_032_783_972_208_265_725, 12_954_103_179_475_586_193]);
+    assert_eq!((0..8).map(|_| rng.next_u8()).collect::<Vec<_>>(), [224, 127, 110, 65, 12, 204, 136, 251]);
+}
+
+#[test]
+fn state_resumes_the_sequence() {
+    let mut rng = Xorshift::new(7);
+    sequence(&mut rng, 10);
+    let mut resumed = Xorshift::from_state(rng.state());
+    assert_eq!(sequence(&mut resumed, 10), sequence(&mut rng, 10));
+
+    // A zero state would only ever produce zeroes.
+    assert_eq!(Xorshift::from_state(0).state(), 1);
+    assert_ne!(Xorshift::from_state(0).next_u64(), 0);
+}
+
+#[test]
+fn rnd_follows_the_cpu_seed() {
+    let rom = assemble("
+        RND V0, 0xff
+        RND V1, 0xff
+        RND V2, 0xff
+        RND V3, 0x0f
+        EXIT
+    ").unwrap();
+    let run = |seed| {
+        let mut cpu = Cpu::new(&rom).unwrap();
+        cpu.set_seed(seed);
+        Scheduler::default().frame(&mut cpu).unwrap();
+        assert!(cpu.halted());
+        cpu.v()[..4].to_vec()
+    };
+
+    assert_eq!(run(1234), run(1234));
+    assert_ne!(run(1234), run(4321));
+    assert!(run(99)[3] <= 0x0f);
+}
+
+#[test]
+fn unseeded_cpus_differ() {
+    assert_ne!(random_seed(), random_seed());
+    let rom = assemble("CLS").unwrap();
+    assert_ne!(Cpu::new(&rom).unwrap().seed(), Cpu::new(&rom).unwrap().seed());
+}
@@ -0,0 +1,89 @@
+use rschip8::prelude::*;
+
+// Touches registers, the timers, memory, the screen and the RNG.
+fn rom() -> Vec<u8> {
+    assemble("
+    loop:
+        RND V0, 0x3f
+        LD DT, V0
+        LD F, V1
+        DRW V0, V2, 5
+        ADD V1, 1
+        ADD V2, 3
+        LD I, 0x400
+        LD [I], V2
+        JP loop
+    ").unwrap()
+}
+
+// Runs `frames` frames the way the frontend does, pushing a snapshot at the
+// start of each. Returns the state at the start of every frame and before
+// every instruction.
+fn run(cpu: &mut Cpu, rewind: &mut Rewind, frames: u32) -> (Vec<SaveState>, Vec<SaveState>) {
+    let mut scheduler = Scheduler::default();
+    let mut starts = Vec::new();
+    let mut states = Vec::new();
+    for _ in 0..frames {
+        rewind.push(cpu);
+        starts.push(cpu.save_state());
+        for _ in 0..scheduler.instructions_per_frame() {
+            states.push(cpu.save_state());
+            let (_, _, op) = cpu.current();
+            cpu.step(&op).unwrap();
+        }
+        cpu.tick_timers();
+    }
+    (starts, states)
+}
+
+fn new_cpu() -> Cpu {
+    let mut cpu = Cpu::new(&rom()).unwrap();
+    cpu.set_seed(5);
+    cpu
+}
+
+#[test]
+fn step_back_restores_the_exact_prior_state() {
+    let mut cpu = new_cpu();
+    let mut rewind = Rewind::default();
+    let (_, states) = run(&mut cpu, &mut rewind, 5);
+
+    // Across frame boundaries too, where the timers ticked in between.
+    for expected in states.iter().rev() {
+        assert!(rewind.step_back(&mut cpu).unwrap());
+        assert!(cpu.save_state() == *expected, "differs at cycle {}", expected.cycles);
+    }
+    assert!(!rewind.step_back(&mut cpu).unwrap());
+}
+
+#[test]
+fn rewind_walks_back_a_frame_at_a_time() {
+    let mut cpu = new_cpu();
+    let mut rewind = Rewind::default();
+    let (starts, _) = run(&mut cpu, &mut rewind, 8);
+    assert_eq!(rewind.len(), 8);
+
+    for expected in starts.iter().rev() {
+        assert!(rewind.rewind(&mut cpu));
+        assert!(cpu.save_state() == *expected, "differs at cycle {}", expected.cycles);
+    }
+    assert!(!rewind.rewind(&mut cpu));
+    assert!(rewind.is_empty());
+}
+
+#[test]
+fn the_budget_drops_the_oldest_snapshots() {
+    let mut cpu = new_cpu();
+    let mut rewind = Rewind::new(2048);
+    run(&mut cpu, &mut rewind, 100);
+
+    assert!(rewind.size() <= rewind.budget());
+    assert!(rewind.len() > 1 && rewind.len() < 100, "{} snapshots", rewind.len());
+
+    // Only as far back as the oldest snapshot kept.
+    let mut steps = 0;
+    while rewind.step_back(&mut cpu).unwrap() {
+        steps += 1;
+    }
+    assert!(steps > 0 && cpu.cycles() > 0);
+}
@@ -0,0 +1,83 @@
+use rschip8::prelude::*;
+
+// A whole number of instructions per frame, so no scheduler carries over.
+const CLOCK_HZ: u32 = 600;
+
+fn rom() -> Vec<u8> {
+    assemble("
+    loop:
+        RND V0, 0xff
+        LD I, buffer
+        LD [I], V0
+        CALL draw
+        JP loop
+    draw:
+        LD F, V0
+        DRW V1, V2, 5
+        ADD V1, 3
+        RET
+    buffer:
+        db 0
+    ").unwrap()
+}
+
+fn running(frames: u32) -> Cpu {
+    let mut cpu = Cpu::new(&rom()).unwrap();
+    cpu.set_seed(99);
+    cpu.set_quirks(Quirks::xochip());
+    cpu.press(5);
+    let mut scheduler = Scheduler::new(CLOCK_HZ);
+    for _ in 0..frames {
+        scheduler.frame(&mut cpu).unwrap();
+    }
+    cpu
+}
+
+#[test]
+fn encoding_round_trips_and_resumes() {
+    let mut cpu = running(10);
+    let state = cpu.save_state();
+    let decoded = SaveState::decode(&state.encode().unwrap()).unwrap();
+    assert!(decoded == state, "decoded state differs");
+
+    // Resuming from the state matches carrying on without it.
+    let mut scheduler = Scheduler::new(CLOCK_HZ);
+    let mut resumed = Cpu::new(&rom()).unwrap();
+    resumed.load_state(&decoded).unwrap();
+    for _ in 0..10 {
+        scheduler.frame(&mut cpu).unwrap();
+        scheduler.frame(&mut resumed).unwrap();
+    }
+    assert!(resumed.save_state() == cpu.save_state(), "resumed machine diverged");
+}
+
+#[test]
+fn other_roms_and_versions_are_rejected() {
+    let data = running(3).save_state().encode().unwrap();
+
+    let mut other = Cpu::new(&assemble("CLS").unwrap()).unwrap();
+    let state = SaveState::decode(&data).unwrap();
+    let error = other.load_state(&state).unwrap_err();
+    assert!(matches!(error, StateError::RomMismatch { .. }), "{error}");
+
+    assert_eq!(data[4..6], [1, 0], "first released format is version 1");
+    let mut newer = data.clone();
+    newer[4..6].copy_from_slice(&(STATE_VERSION + 1).to_le_bytes());
+    assert_eq!(SaveState::decode(&newer).unwrap_err(), StateError::UnsupportedVersion(STATE_VERSION + 1));
+    assert_eq!(SaveState::decode(b"C8MV").unwrap_err(), StateError::BadMagic);
+    assert_eq!(SaveState::decode(&data[..data.len() - 1]).unwrap_err(), StateError::Truncated);
+}
+
+// Stepping off the top of memory lands back at 0, so the state still saves.
+#[test]
+fn pc_wraps_at_the_top_of_memory() {
+    let mut cpu = Cpu::new(&rom()).unwrap();
+    let top = cpu.bus().len() - 2;
+    cpu.bus_mut().load(top, &[0x60, 0x01]);
+    cpu.set_pc(top);
+    let (_, _, op) = cpu.current();
+    cpu.step(&op).unwrap();
+
+    assert_eq!(cpu.pc(), 0);
+    assert!(cpu.save_state().encode().is_ok());
+}
@@ -0,0 +1,40 @@
+use rschip8::prelude::*;
+
+// Counts instructions in V1/V2 forever.
+fn rom() -> Vec<u8> {
+    assemble("
+    loop:
+        ADD V1, 1
+        SE V1, 0
+        JP loop
+        ADD V2, 1
+        JP loop
+    ").unwrap()
+}
+
+#[test]
+fn timers_tick_once_per_frame_at_any_clock() {
+    for clock_hz in [MIN_CLOCK_HZ, DEFAULT_CLOCK_HZ, 5000, MAX_CLOCK_HZ] {
+        let mut cpu = Cpu::new(&rom()).unwrap();
+        *cpu.timers_mut() = Timers { delay: 60, sound: 60 };
+        let mut scheduler = Scheduler::new(clock_hz);
+        for frame in 1..=70u8 {
+            scheduler.frame(&mut cpu).unwrap();
+            let left = 60u8.saturating_sub(frame);
+            assert_eq!(*cpu.timers(), Timers { delay: left, sound: left }, "{clock_hz} Hz, frame {frame}");
+            assert_eq!(cpu.timers().sounding(), left > 0);
+        }
+        assert_eq!(cpu.cycles(), u64::from(clock_hz) * 70 / u64::from(TIMER_HZ), "{clock_hz} Hz");
+    }
+}
+
+#[test]
+fn instructions_per_frame_carry_the_remainder() {
+    let mut scheduler = Scheduler::new(700);
+    let counts = (0..6).map(|_| scheduler.instructions_per_frame()).collect::<Vec<_>>();
+    assert_eq!(counts, [11, 12, 12, 11, 12, 12]);
+
+    // A second's worth of frames runs the whole clock.
+    let mut scheduler = Scheduler::new(1234);
+    assert_eq!((0..TIMER_HZ).map(|_| scheduler.instructions_per_frame()).sum::<u32>(), 1234);
+}
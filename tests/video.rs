@@ -0,0 +1,61 @@
+use rschip8::prelude::*;
+
+fn lit(video: &Video) -> Vec<(i32, i32)> {
+    let mut pixels = Vec::new();
+    for y in 0..video.height() {
+        for x in 0..video.width() {
+            if video.ram[video.map_idx(x, y)] != 0 {
+                pixels.push((x, y));
+            }
+        }
+    }
+    pixels
+}
+
+#[test]
+fn scroll_up_moves_rows_and_blanks_the_bottom() {
+    let mut video = Video::new();
+    video.draw(&[0x80], 3, 5, true);
+    video.draw(&[0x80], 3, 31, true);
+
+    video.scroll_up(2);
+    assert_eq!(lit(&video), [(3, 3), (3, 29)]);
+
+    // Rows scrolled off the top are gone.
+    video.scroll_up(4);
+    assert_eq!(lit(&video), [(3, 25)]);
+}
+
+#[test]
+fn scroll_up_only_moves_the_selected_planes() {
+    let mut video = Video::new();
+    video.set_planes(3);
+    video.draw(&[0x80, 0x80], 0, 4, true);
+
+    video.set_planes(2);
+    video.scroll_up(1);
+    assert_eq!(video.ram[video.map_idx(0, 4)], 1);
+    assert_eq!(video.ram[video.map_idx(0, 3)], 2);
+}
+
+#[test]
+fn cpu_executes_scroll_up() {
+    let rom = assemble("
+        LD I, sprite
+        LD V0, 10
+        DRW V0, V0, 1
+        SCU 4
+        EXIT
+    sprite:
+        db 0x80
+    ").unwrap();
+    assert_eq!(rom[6..8], [0x00, 0xd4]);
+
+    let mut cpu = Cpu::new(&rom).unwrap();
+    cpu.set_quirks(Quirks::xochip());
+    while !cpu.halted() {
+        let (_, _, op) = cpu.current();
+        cpu.step(&op).unwrap();
+    }
+    assert_eq!(lit(&cpu.video), [(10, 6)]);
+}